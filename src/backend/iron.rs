@@ -49,6 +49,9 @@ impl<'a, 'b> backend::Request for iron::Request<'a, 'b> {
     fn body(&self) -> &request::Body { &self.body }
     fn body_mut(&mut self) -> &mut request::Body { &mut self.body }
     fn read_to_end(&mut self) -> Result<Option<String>, Box<errors::Error + Send>> {
+        if let Some(body) = self.extensions().get::<backend::BodyKey>() {
+            return Ok(Some(body.clone()));
+        }
         self.get::<bodyparser::Raw>().map_err(|err| Box::new(err) as Box<errors::Error + Send>)
     }
 }
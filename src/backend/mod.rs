@@ -1,5 +1,5 @@
 pub use self::simple_request::{SimpleRequest};
-pub use self::request::{Request, AsUrl};
+pub use self::request::{Request, AsUrl, BodyKey};
 pub use self::response::{Response, ResponseBody, WriteBody};
 
 pub use self::iron::{
@@ -2,6 +2,7 @@ use std::fmt;
 use std::io;
 use std::net;
 use url;
+use typemap;
 
 use framework::media;
 
@@ -13,6 +14,14 @@ pub trait Body: io::Read { }
 
 impl Body for Box<io::Read + 'static> { }
 
+/// The body of a request once it is read or decoded, `read_to_end` returns
+/// it instead of reading the body again
+pub struct BodyKey;
+
+impl typemap::Key for BodyKey {
+    type Value = String;
+}
+
 pub trait AsUrl {
     fn scheme(&self) -> &str;
     fn host(&self) -> url::Host<&str>;
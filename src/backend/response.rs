@@ -1,3 +1,5 @@
+use std::io;
+
 use server::header;
 use server::mime;
 use server::status;
@@ -56,6 +58,43 @@ impl Response {
     pub fn replace_body(&mut self, body: Box<WriteBody>) {
         self.body = Some(body)
     }
+
+    /// Adds `name` to the `Vary` header, keeping the names already there
+    pub fn add_vary(&mut self, name: &str) {
+        let mut names: Vec<String> = self.headers.get_raw("Vary")
+            .map(|values| values.iter()
+                .filter_map(|value| String::from_utf8(value.clone()).ok())
+                .flat_map(|value| value.split(',').map(|name| name.trim().to_string()).collect::<Vec<String>>())
+                .filter(|name| !name.is_empty())
+                .collect())
+            .unwrap_or(vec![]);
+
+        if !names.iter().any(|existing| existing.eq_ignore_ascii_case(name)) {
+            names.push(name.to_string());
+        }
+        self.headers.set_raw("Vary", vec![names.join(", ").into_bytes()]);
+    }
+
+    /// Writes the body into memory and takes it out of the response
+    pub fn take_body_bytes(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.body.take() {
+            Some(mut body) => {
+                let mut bytes = Vec::new();
+                try!(body.write_body(&mut ResponseBody::new(&mut bytes)));
+                Ok(Some(bytes))
+            },
+            None => Ok(None)
+        }
+    }
 }
 
 impl_extensible!(Response);
+
+#[test]
+fn it_merges_vary_headers() {
+    let mut response = Response::new(status::StatusCode::Ok);
+    response.headers.set_raw("Vary", vec![b"Origin".to_vec()]);
+    response.add_vary("Accept-Encoding");
+    response.add_vary("accept-encoding");
+    assert_eq!(response.headers.get_raw("Vary").unwrap()[0], b"Origin, Accept-Encoding".to_vec());
+}
@@ -10,7 +10,7 @@ use server::method;
 use server::header;
 use super::request;
 use super::super::errors;
-use backend::{Request, Url, AsUrl, WrapUrl};
+use backend::{Request, Url, AsUrl, WrapUrl, BodyKey};
 
 #[allow(dead_code)]
 pub struct SimpleRequest {
@@ -49,11 +49,15 @@ impl<'a> Request for SimpleRequest {
     }
 
     fn read_to_end(&mut self) -> Result<Option<String>, Box<errors::Error + Send>> {
+        if let Some(body) = self.ext.get::<BodyKey>() {
+            return Ok(Some(body.clone()));
+        }
+
         let mut bytes = Vec::new();
         self.body.read_to_end(&mut bytes).unwrap();
-        String::from_utf8(bytes)
-            .map(|body| Some(body))
-            .map_err(|err| Box::new(err) as Box<errors::Error + Send>)
+        let body = try!(String::from_utf8(bytes).map_err(|err| Box::new(err) as Box<errors::Error + Send>));
+        self.ext.insert::<BodyKey>(body.clone());
+        Ok(Some(body))
     }
 }
 
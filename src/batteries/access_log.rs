@@ -0,0 +1,353 @@
+use std::io;
+use std::net;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use jsonway;
+use url::percent_encoding::percent_decode;
+
+use backend;
+use framework;
+use json::{JsonValue, ToJson};
+use batteries::request_id::RequestIdExt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Common Log Format followed by the route, version, latency, request id and headers
+    Common,
+    /// One JSON object per line
+    JsonLines
+}
+
+pub type Writer = Box<Fn(&str) + 'static + Send + Sync>;
+
+const REDACTED: &'static str = "[REDACTED]";
+
+/// A handled request
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub time: SystemTime,
+    pub remote_addr: net::SocketAddr,
+    pub method: String,
+    /// Requested path with the query string, redacted
+    pub path: String,
+    /// Route template of the endpoint, if one matched
+    pub route: Option<String>,
+    pub version: Option<String>,
+    pub status: u16,
+    /// Bytes of the response body written to the client, if it was written
+    pub size: Option<u64>,
+    pub latency: Duration,
+    pub request_id: Option<String>,
+    /// Logged request headers, redacted
+    pub headers: Vec<(String, String)>
+}
+
+impl Entry {
+    pub fn latency_ms(&self) -> f64 {
+        self.latency.as_secs() as f64 * 1000.0 + self.latency.subsec_nanos() as f64 / 1e6
+    }
+
+    pub fn to_common(&self) -> String {
+        let mut line = format!("{} - - [{}] {} {} {} {} {} {:.3} {}",
+            self.remote_addr.ip(),
+            common_time(self.time),
+            quote(&format!("{} {}", self.method, self.path)),
+            self.status,
+            self.size.map_or("-".to_string(), |size| size.to_string()),
+            self.route.as_ref().map_or("-".to_string(), |route| quote(route)),
+            self.version.as_ref().map_or("-".to_string(), |version| quote(version)),
+            self.latency_ms(),
+            self.request_id.as_ref().map_or("-".to_string(), |id| quote(id)));
+
+        for &(ref name, ref value) in self.headers.iter() {
+            line.push(' ');
+            line.push_str(&quote(&format!("{}: {}", name, value)));
+        }
+
+        line
+    }
+}
+
+impl ToJson for Entry {
+    fn to_json(&self) -> JsonValue {
+        jsonway::object(|json| {
+            json.set("time", unix_seconds(self.time));
+            json.set("remote_addr", self.remote_addr.ip().to_string());
+            json.set("method", self.method.clone());
+            json.set("path", self.path.clone());
+            json.set("route", self.route.clone());
+            json.set("version", self.version.clone());
+            json.set("status", self.status);
+            json.set("size", self.size);
+            json.set("latency_ms", self.latency_ms());
+            json.set("request_id", self.request_id.clone());
+            if !self.headers.is_empty() {
+                json.object("headers", |json| {
+                    for &(ref name, ref value) in self.headers.iter() {
+                        json.set(name.clone(), value.clone());
+                    }
+                });
+            }
+        }).unwrap()
+    }
+}
+
+/// Access log settings.
+///
+/// Lines are written to the `rustless::access` log target unless a writer is
+/// set. Values of the redacted query params and headers are replaced with
+/// `[REDACTED]`, nested params like `user[password]` are matched by their last
+/// key. Keys are percent-decoded before they are matched.
+pub struct AccessLog {
+    format: Format,
+    headers: Vec<String>,
+    redacted_headers: Vec<String>,
+    redacted_params: Vec<String>,
+    writer: Writer
+}
+
+impl AccessLog {
+    pub fn new() -> AccessLog {
+        AccessLog {
+            format: Format::Common,
+            headers: vec![],
+            redacted_headers: to_lowercase(&["Authorization", "Proxy-Authorization", "Cookie", "X-Api-Key"]),
+            redacted_params: to_lowercase(&["password", "token", "access_token", "api_key", "secret"]),
+            writer: Box::new(|line| info!(target: "rustless::access", "{}", line))
+        }
+    }
+
+    pub fn build<F>(builder: F) -> AccessLog where F: FnOnce(&mut AccessLog) {
+        let mut access_log = AccessLog::new();
+        builder(&mut access_log);
+
+        access_log
+    }
+
+    pub fn format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    /// Request headers to include in the entries
+    pub fn log_headers(&mut self, headers: &[&str]) {
+        self.headers = headers.iter().map(|header| header.to_string()).collect();
+    }
+
+    pub fn redact_headers(&mut self, headers: &[&str]) {
+        self.redacted_headers.extend(to_lowercase(headers));
+    }
+
+    pub fn redact_params(&mut self, params: &[&str]) {
+        self.redacted_params.extend(to_lowercase(params));
+    }
+
+    pub fn writer<F: 'static>(&mut self, writer: F) where F: Fn(&str) + Send+Sync {
+        self.writer = Box::new(writer);
+    }
+
+    fn redact_query(&self, query: &str) -> String {
+        let pairs: Vec<String> = query.split('&').map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let key = parts.next().unwrap_or("");
+            match parts.next() {
+                Some(_) if self.redacted_params.contains(&param_name(key)) => format!("{}={}", key, REDACTED),
+                _ => pair.to_string()
+            }
+        }).collect();
+
+        pairs.join("&")
+    }
+
+    /// Describes the request, without the size of the response body
+    pub fn entry(&self, req: &backend::Request, response: &backend::Response, started: Instant) -> Entry {
+        let mut path = format!("/{}", req.url().path().join("/"));
+        if let Some(query) = req.url().query() {
+            path.push('?');
+            path.push_str(&self.redact_query(query));
+        }
+
+        let route = req.ext().get::<framework::MatchedRoute>();
+        let headers = self.headers.iter().filter_map(|name| {
+            req.headers().get_raw(name).and_then(|values| values.first()).map(|value| {
+                let value = if self.redacted_headers.contains(&name.to_lowercase()) {
+                    REDACTED.to_string()
+                } else {
+                    String::from_utf8_lossy(value).into_owned()
+                };
+                (name.clone(), value)
+            })
+        }).collect();
+
+        Entry {
+            time: SystemTime::now(),
+            remote_addr: *req.remote_addr(),
+            method: req.method().to_string(),
+            path: path,
+            route: route.map(|route| route.route.clone()),
+            version: route.and_then(|route| route.version.clone()),
+            status: response.status.to_u16(),
+            size: None,
+            latency: started.elapsed(),
+            request_id: req.request_id().map(|id| id.to_string()),
+            headers: headers
+        }
+    }
+
+    pub fn line(&self, entry: &Entry) -> String {
+        match self.format {
+            Format::Common => entry.to_common(),
+            Format::JsonLines => entry.to_json().to_string()
+        }
+    }
+
+    pub fn write(&self, entry: &Entry) {
+        (self.writer)(&self.line(entry));
+    }
+}
+
+/// Counts the bytes written by the inner body
+struct CountingWriter<'a, 'b: 'a> {
+    inner: &'a mut backend::ResponseBody<'b>,
+    written: u64
+}
+
+impl<'a, 'b> io::Write for CountingWriter<'a, 'b> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = try!(self.inner.write(buf));
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wraps the response body and writes the entry once the body is written to
+/// the client or dropped, with the number of bytes written and the latency
+/// up to then
+struct LoggedBody {
+    body: Option<Box<backend::WriteBody>>,
+    entry: Entry,
+    started: Instant,
+    access_log: Arc<AccessLog>
+}
+
+impl backend::WriteBody for LoggedBody {
+    fn write_body(&mut self, res: &mut backend::ResponseBody) -> io::Result<()> {
+        let mut counter = CountingWriter { inner: res, written: 0 };
+        let result = match self.body {
+            Some(ref mut body) => body.write_body(&mut backend::ResponseBody::new(&mut counter)),
+            None => Ok(())
+        };
+        self.entry.size = Some(self.entry.size.unwrap_or(0) + counter.written);
+        result
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        self.entry.latency = self.started.elapsed();
+        self.access_log.write(&self.entry);
+    }
+}
+
+fn to_lowercase(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_lowercase()).collect()
+}
+
+/// `user[password]` is matched as `password`, `user%5Bpassword%5D` too
+fn param_name(key: &str) -> String {
+    let key = percent_decode(key.replace('+', " ").as_bytes()).decode_utf8_lossy().into_owned();
+    let key = key.trim_right_matches(']');
+    let name = match key.rfind('[') {
+        Some(pos) => &key[pos + 1..],
+        None => key
+    };
+    name.to_lowercase()
+}
+
+/// Quotes a field, escaping characters that could forge log lines
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            ch if ch.is_control() => quoted.push_str(&format!("\\x{:02x}", ch as u32)),
+            ch => quoted.push(ch)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unix_seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9)
+        .unwrap_or(0.0)
+}
+
+/// Formats a time as `10/Oct/2000:13:55:36 +0000`
+fn common_time(time: SystemTime) -> String {
+    const MONTHS: [&'static str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day, MONTHS[(month - 1) as usize], year, secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60)
+}
+
+/// Logs every request handled by the application, once its response is written
+pub fn enable(app: &mut framework::Application, access_log: AccessLog) {
+    let access_log = Arc::new(access_log);
+    app.on_response(move |req, response| {
+        let started = req.ext().get::<framework::RequestStart>().cloned().unwrap_or_else(Instant::now);
+        let entry = access_log.entry(req, response, started);
+        let body = response.body.take();
+        response.replace_body(Box::new(LoggedBody {
+            body: body,
+            entry: entry,
+            started: started,
+            access_log: access_log.clone()
+        }));
+    });
+}
+
+#[test]
+fn it_formats_common_log_time() {
+    assert_eq!(common_time(UNIX_EPOCH), "01/Jan/1970:00:00:00 +0000");
+    assert_eq!(common_time(UNIX_EPOCH + Duration::from_secs(971186136)), "10/Oct/2000:13:55:36 +0000");
+    assert_eq!(common_time(UNIX_EPOCH + Duration::from_secs(1709164800)), "29/Feb/2024:00:00:00 +0000");
+}
+
+#[test]
+fn it_redacts_query_params() {
+    let access_log = AccessLog::build(|access_log| access_log.redact_params(&["card"]));
+    assert_eq!(access_log.redact_query("q=shoes&token=abc&user[password]=x&Card=1&flag"),
+        "q=shoes&token=[REDACTED]&user[password]=[REDACTED]&Card=[REDACTED]&flag");
+}
+
+#[test]
+fn it_redacts_encoded_query_params() {
+    let access_log = AccessLog::build(|access_log| access_log.redact_params(&["card number"]));
+    assert_eq!(access_log.redact_query("user%5Bpassword%5D=secret&pass%77ord=secret&card+number=4111&q=a+b"),
+        "user%5Bpassword%5D=[REDACTED]&pass%77ord=[REDACTED]&card+number=[REDACTED]&q=a+b");
+}
+
+#[test]
+fn it_escapes_quoted_fields() {
+    assert_eq!(quote("GET /a\"b\n"), "\"GET /a\\\"b\\x0a\"");
+}
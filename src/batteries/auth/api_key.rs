@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use queryst;
+use jsonway;
+
+use backend;
+use errors;
+use framework::{self, Nesting};
+use super::{AuthExt, Principal};
+
+/// Where clients put their key
+pub enum KeyLocation {
+    Header(String),
+    Query(String)
+}
+
+impl KeyLocation {
+    fn swagger_in(&self) -> &'static str {
+        match self {
+            &KeyLocation::Header(_) => "header",
+            &KeyLocation::Query(_) => "query"
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            &KeyLocation::Header(ref name) => name,
+            &KeyLocation::Query(ref name) => name
+        }
+    }
+
+    /// The key sent with the request, if any
+    pub fn extract(&self, req: &backend::Request) -> Option<String> {
+        match self {
+            &KeyLocation::Header(ref name) => {
+                req.headers().get_raw(name)
+                    .and_then(|values| values.first())
+                    .and_then(|value| String::from_utf8(value.clone()).ok())
+            },
+            &KeyLocation::Query(ref name) => {
+                req.url().query()
+                    .and_then(|query| queryst::parse(query).ok())
+                    .and_then(|params| params.find(name).and_then(|key| key.as_str()).map(|key| key.to_string()))
+            }
+        }
+    }
+}
+
+/// The owner of a key and the scopes granted to it
+#[derive(Clone, Debug)]
+pub struct KeyInfo {
+    pub owner: String,
+    pub scopes: Vec<String>
+}
+
+impl KeyInfo {
+    pub fn new(owner: &str, scopes: &[&str]) -> KeyInfo {
+        KeyInfo {
+            owner: owner.to_string(),
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect()
+        }
+    }
+}
+
+/// Looks up issued keys
+pub trait KeyStore: Send + Sync {
+    fn find(&self, key: &str) -> Option<KeyInfo>;
+}
+
+pub struct MemoryStore {
+    keys: HashMap<String, KeyInfo>
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            keys: HashMap::new()
+        }
+    }
+
+    pub fn insert(&mut self, key: &str, info: KeyInfo) {
+        self.keys.insert(key.to_string(), info);
+    }
+}
+
+impl KeyStore for MemoryStore {
+    fn find(&self, key: &str) -> Option<KeyInfo> {
+        self.keys.get(key).cloned()
+    }
+}
+
+/// Keys listed in a text file, one `<key> <owner> [<scope> ...]` entry per line.
+///
+/// Empty lines and lines starting with `#` are ignored. Call `reload` to pick
+/// up changes of the file.
+pub struct FileStore {
+    path: PathBuf,
+    keys: RwLock<HashMap<String, KeyInfo>>
+}
+
+impl FileStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FileStore> {
+        let store = FileStore {
+            path: path.as_ref().to_path_buf(),
+            keys: RwLock::new(HashMap::new())
+        };
+        try!(store.reload());
+
+        Ok(store)
+    }
+
+    pub fn reload(&self) -> io::Result<()> {
+        let mut content = String::new();
+        try!(fs::File::open(&self.path).and_then(|mut file| file.read_to_string(&mut content)));
+
+        let mut keys = HashMap::new();
+        for line in content.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some(key), Some(owner)) => {
+                    keys.insert(key.to_string(), KeyInfo {
+                        owner: owner.to_string(),
+                        scopes: fields.map(|scope| scope.to_string()).collect()
+                    });
+                },
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid key entry `{}`", line)))
+            }
+        }
+
+        *self.keys.write().unwrap() = keys;
+        Ok(())
+    }
+}
+
+impl KeyStore for FileStore {
+    fn find(&self, key: &str) -> Option<KeyInfo> {
+        self.keys.read().unwrap().get(key).cloned()
+    }
+}
+
+/// API key authentication.
+///
+/// The owner of a known key becomes the principal of the request, with the
+/// scopes granted to the key.
+pub struct ApiKey {
+    location: KeyLocation,
+    store: Box<KeyStore>
+}
+
+impl ApiKey {
+    pub fn new<S: KeyStore + 'static>(location: KeyLocation, store: S) -> ApiKey {
+        ApiKey {
+            location: location,
+            store: Box::new(store)
+        }
+    }
+
+    pub fn challenge(&self) -> String {
+        format!("ApiKey {}=\"{}\"", self.location.swagger_in(), self.location.name())
+    }
+
+    /// Stores the principal in the client extensions or fails with `errors::Unauthorized`
+    pub fn authenticate(&self, client: &mut framework::Client) -> backend::HandleSuccessResult {
+        let key = match self.location.extract(client.request) {
+            Some(key) => key,
+            None => return Err(error_response!(errors::Unauthorized::new(&self.challenge(), "API key is required")))
+        };
+
+        match self.store.find(&key) {
+            Some(info) => {
+                let mut principal = Principal::new(&info.owner);
+                principal.scopes = info.scopes;
+                client.set_principal(principal);
+                Ok(())
+            },
+            None => Err(error_response!(errors::Unauthorized::new(&self.challenge(), "API key is invalid")))
+        }
+    }
+
+    /// The `api_key` entry of the Swagger security definitions
+    pub fn scheme(&self) -> framework::SecurityScheme {
+        framework::SecurityScheme::new("api_key", jsonway::object(|scheme| {
+            scheme.set("type", "apiKey".to_string());
+            scheme.set("name", self.location.name().to_string());
+            scheme.set("in", self.location.swagger_in().to_string());
+        }).unwrap())
+    }
+}
+
+/// Requires a known API key for every endpoint of `node`
+pub fn protect<N: Nesting>(node: &mut N, api_key: ApiKey) {
+    node.security_scheme(api_key.scheme());
+    node.before(move |client, _params| api_key.authenticate(client));
+}
@@ -0,0 +1,64 @@
+use jsonway;
+
+use backend;
+use errors;
+use framework::{self, Nesting};
+use server::header;
+use super::{AuthExt, Principal};
+
+pub type Verifier = Box<for<'a> Fn(&framework::Client<'a>, &str, &str) -> Option<Principal> + 'static + Sync + Send>;
+
+/// HTTP Basic authentication as described in RFC 7617.
+///
+/// The verifier receives the user name and the password and returns the
+/// principal for valid credentials.
+pub struct Basic {
+    realm: String,
+    verifier: Verifier
+}
+
+impl Basic {
+    pub fn new<F: 'static>(realm: &str, verifier: F) -> Basic
+    where F: for<'a> Fn(&framework::Client<'a>, &str, &str) -> Option<Principal> + Send+Sync {
+        Basic {
+            realm: realm.to_string(),
+            verifier: Box::new(verifier)
+        }
+    }
+
+    pub fn challenge(&self) -> String {
+        format!("Basic realm=\"{}\"", self.realm)
+    }
+
+    /// Stores the principal in the client extensions or fails with `errors::Unauthorized`
+    pub fn authenticate(&self, client: &mut framework::Client) -> backend::HandleSuccessResult {
+        let principal = match client.request.headers().get::<header::Authorization<header::Basic>>() {
+            Some(&header::Authorization(ref credentials)) => {
+                let password = credentials.password.as_ref().map(|p| &p[..]).unwrap_or("");
+                (self.verifier)(client, &credentials.username, password)
+            },
+            None => return Err(error_response!(errors::Unauthorized::new(&self.challenge(), "Authentication is required")))
+        };
+
+        match principal {
+            Some(principal) => {
+                client.set_principal(principal);
+                Ok(())
+            },
+            None => Err(error_response!(errors::Unauthorized::new(&self.challenge(), "Invalid credentials")))
+        }
+    }
+}
+
+/// The `basic` entry of the Swagger security definitions
+pub fn scheme() -> framework::SecurityScheme {
+    framework::SecurityScheme::new("basic", jsonway::object(|scheme| {
+        scheme.set("type", "basic".to_string());
+    }).unwrap())
+}
+
+/// Requires valid credentials for every endpoint of `node`
+pub fn protect<N: Nesting>(node: &mut N, basic: Basic) {
+    node.security_scheme(scheme());
+    node.before(move |client, _params| basic.authenticate(client));
+}
@@ -0,0 +1,408 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use ring::{hmac, signature};
+use rustc_serialize::base64::FromBase64;
+use serde_json;
+use typemap;
+use jsonway;
+
+use backend;
+use errors;
+use framework::{self, Nesting};
+use json::{self, JsonValue};
+use server::header;
+use super::{AuthExt, Principal};
+
+/// A key that verifies token signatures
+pub enum Key {
+    /// HMAC using SHA-256 with a shared secret
+    Hs256(Vec<u8>),
+    /// RSASSA-PKCS1-v1_5 using SHA-256 with a DER encoded `RSAPublicKey`
+    Rs256(Vec<u8>)
+}
+
+impl Key {
+    pub fn hs256(secret: &[u8]) -> Key {
+        Key::Hs256(secret.to_vec())
+    }
+
+    /// Reads an RSA public key from a `PUBLIC KEY` or `RSA PUBLIC KEY` PEM document
+    pub fn rs256_pem(pem: &str) -> Result<Key, KeyError> {
+        let (label, der) = try!(decode_pem(pem));
+        match &label[..] {
+            "RSA PUBLIC KEY" => Ok(Key::Rs256(der)),
+            "PUBLIC KEY" => match spki_to_rsa_public_key(&der) {
+                Some(der) => Ok(Key::Rs256(der)),
+                None => Err(KeyError::Pem("The public key is not an RSA key".to_string()))
+            },
+            _ => Err(KeyError::Pem(format!("Unexpected PEM label `{}`", label)))
+        }
+    }
+
+    pub fn rs256_pem_file<P: AsRef<Path>>(path: P) -> Result<Key, KeyError> {
+        let mut pem = String::new();
+        try!(fs::File::open(path).and_then(|mut file| file.read_to_string(&mut pem)).map_err(KeyError::Io));
+        Key::rs256_pem(&pem)
+    }
+
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            &Key::Hs256(_) => "HS256",
+            &Key::Rs256(_) => "RS256"
+        }
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            &Key::Hs256(ref secret) => {
+                let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+                hmac::verify(&key, message, signature).is_ok()
+            },
+            &Key::Rs256(ref der) => {
+                let key = signature::UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, der);
+                key.verify(message, signature).is_ok()
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyError {
+    Io(io::Error),
+    Pem(String)
+}
+
+impl StdError for KeyError {
+    fn description(&self) -> &str {
+        match self {
+            &KeyError::Io(_) => "Key can't be read",
+            &KeyError::Pem(ref reason) => reason
+        }
+    }
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &KeyError::Io(ref err) => write!(formatter, "Key can't be read: {}", err),
+            &KeyError::Pem(ref reason) => reason.fmt(formatter)
+        }
+    }
+}
+
+/// Reasons to reject a token, reported in the `error_description` of the challenge
+#[derive(Debug, PartialEq)]
+pub enum TokenError {
+    Malformed,
+    UnsupportedAlgorithm,
+    InvalidSignature,
+    Expired,
+    NotYetValid,
+    InvalidAudience,
+    InvalidIssuer
+}
+
+impl TokenError {
+    pub fn description(&self) -> &'static str {
+        match self {
+            &TokenError::Malformed => "The access token is malformed",
+            &TokenError::UnsupportedAlgorithm => "The access token is signed with an unsupported algorithm",
+            &TokenError::InvalidSignature => "The access token signature is invalid",
+            &TokenError::Expired => "The access token expired",
+            &TokenError::NotYetValid => "The access token is not valid yet",
+            &TokenError::InvalidAudience => "The access token is issued for another audience",
+            &TokenError::InvalidIssuer => "The access token is issued by an unknown issuer"
+        }
+    }
+}
+
+/// Verified claims of the token of a request
+pub struct Claims(pub json::Object);
+
+impl Claims {
+    pub fn get(&self, name: &str) -> Option<&JsonValue> {
+        self.0.get(name)
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.get("sub").and_then(|sub| sub.as_str())
+    }
+
+    /// Scopes of the `scope` (space separated) or `scp` (array) claim
+    pub fn scopes(&self) -> Vec<String> {
+        match self.get("scope").and_then(|scope| scope.as_str()) {
+            Some(scope) => scope.split(' ').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect(),
+            None => strings(self.get("scp"))
+        }
+    }
+
+    pub fn roles(&self) -> Vec<String> {
+        strings(self.get("roles"))
+    }
+}
+
+pub struct ClaimsKey;
+
+impl typemap::Key for ClaimsKey {
+    type Value = Claims;
+}
+
+pub trait ClaimsExt {
+    fn claims(&self) -> Option<&Claims>;
+}
+
+impl<'a> ClaimsExt for framework::Client<'a> {
+    fn claims(&self) -> Option<&Claims> {
+        self.ext.get::<ClaimsKey>()
+    }
+}
+
+/// Bearer token authentication with JSON Web Tokens.
+///
+/// Tokens are accepted when one of the keys verifies their signature and the
+/// `exp`, `nbf`, `aud` and `iss` claims pass the configured checks.
+pub struct Jwt {
+    keys: Vec<Key>,
+    realm: String,
+    audience: Option<String>,
+    issuer: Option<String>,
+    leeway: u64
+}
+
+impl Jwt {
+    pub fn new() -> Jwt {
+        Jwt {
+            keys: vec![],
+            realm: "api".to_string(),
+            audience: None,
+            issuer: None,
+            leeway: 0
+        }
+    }
+
+    pub fn build<F>(builder: F) -> Jwt where F: FnOnce(&mut Jwt) {
+        let mut jwt = Jwt::new();
+        builder(&mut jwt);
+
+        jwt
+    }
+
+    pub fn key(&mut self, key: Key) {
+        self.keys.push(key);
+    }
+
+    pub fn realm(&mut self, realm: &str) {
+        self.realm = realm.to_string();
+    }
+
+    pub fn audience(&mut self, audience: &str) {
+        self.audience = Some(audience.to_string());
+    }
+
+    pub fn issuer(&mut self, issuer: &str) {
+        self.issuer = Some(issuer.to_string());
+    }
+
+    /// Seconds of clock skew tolerated by the `exp` and `nbf` checks
+    pub fn leeway(&mut self, seconds: u64) {
+        self.leeway = seconds;
+    }
+
+    /// Verifies a compact serialized token at the time `now` (seconds since the epoch)
+    pub fn decode(&self, token: &str, now: u64) -> Result<Claims, TokenError> {
+        let parts: Vec<&str> = token.split('.').collect();
+        if parts.len() != 3 {
+            return Err(TokenError::Malformed);
+        }
+
+        let header = try!(decode_object(parts[0]));
+        let claims = try!(decode_object(parts[1]));
+        let signature = try!(parts[2].from_base64().map_err(|_| TokenError::Malformed));
+
+        let algorithm = match header.get("alg").and_then(|alg| alg.as_str()) {
+            Some(algorithm) => algorithm,
+            None => return Err(TokenError::Malformed)
+        };
+
+        let message = &token[..parts[0].len() + 1 + parts[1].len()];
+        let mut keys = self.keys.iter().filter(|key| key.algorithm() == algorithm).peekable();
+        if keys.peek().is_none() {
+            return Err(TokenError::UnsupportedAlgorithm);
+        }
+        if !keys.any(|key| key.verify(message.as_bytes(), &signature)) {
+            return Err(TokenError::InvalidSignature);
+        }
+
+        if let Some(exp) = try!(numeric_date(claims.get("exp"))) {
+            if now >= exp.saturating_add(self.leeway) {
+                return Err(TokenError::Expired);
+            }
+        }
+
+        if let Some(nbf) = try!(numeric_date(claims.get("nbf"))) {
+            if now.saturating_add(self.leeway) < nbf {
+                return Err(TokenError::NotYetValid);
+            }
+        }
+
+        if let Some(ref audience) = self.audience {
+            let audiences = match claims.get("aud") {
+                Some(&JsonValue::String(ref aud)) => vec![aud.clone()],
+                aud => strings(aud)
+            };
+            if !audiences.contains(audience) {
+                return Err(TokenError::InvalidAudience);
+            }
+        }
+
+        if let Some(ref issuer) = self.issuer {
+            if claims.get("iss").and_then(|iss| iss.as_str()) != Some(&issuer[..]) {
+                return Err(TokenError::InvalidIssuer);
+            }
+        }
+
+        Ok(Claims(claims))
+    }
+
+    /// The RFC 6750 challenge, with an error code when a token was rejected
+    pub fn challenge(&self, error: Option<&TokenError>) -> String {
+        match error {
+            Some(error) => format!("Bearer realm=\"{}\", error=\"invalid_token\", error_description=\"{}\"",
+                self.realm, error.description()),
+            None => format!("Bearer realm=\"{}\"", self.realm)
+        }
+    }
+
+    /// Stores the claims and the principal in the client extensions or fails with `errors::Unauthorized`
+    pub fn authenticate(&self, client: &mut framework::Client) -> backend::HandleSuccessResult {
+        let result = match client.request.headers().get::<header::Authorization<header::Bearer>>() {
+            Some(&header::Authorization(ref bearer)) => self.decode(&bearer.token, now()),
+            None => return Err(error_response!(errors::Unauthorized::new(&self.challenge(None), "Authentication is required")))
+        };
+
+        match result {
+            Ok(claims) => {
+                let mut principal = Principal::new(claims.subject().unwrap_or(""));
+                principal.scopes = claims.scopes();
+                principal.roles = claims.roles();
+                client.set_principal(principal);
+                client.ext.insert::<ClaimsKey>(claims);
+                Ok(())
+            },
+            Err(error) => {
+                debug!("{}Rejected bearer token: {:?}", framework::log_prefix(client.request), error);
+                Err(error_response!(errors::Unauthorized::new(&self.challenge(Some(&error)), error.description())))
+            }
+        }
+    }
+}
+
+/// The `bearer` entry of the Swagger security definitions.
+///
+/// Swagger 2.0 has no bearer scheme so tokens are described as an API key
+/// passed in the `Authorization` header.
+pub fn scheme() -> framework::SecurityScheme {
+    framework::SecurityScheme::new("bearer", jsonway::object(|scheme| {
+        scheme.set("type", "apiKey".to_string());
+        scheme.set("name", "Authorization".to_string());
+        scheme.set("in", "header".to_string());
+        scheme.set("description", "JSON Web Token prefixed with `Bearer `".to_string());
+    }).unwrap())
+}
+
+/// Requires a valid token for every endpoint of `node`
+pub fn protect<N: Nesting>(node: &mut N, jwt: Jwt) {
+    node.security_scheme(scheme());
+    node.before(move |client, _params| jwt.authenticate(client));
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Seconds since the epoch of a `NumericDate` claim. Fractional seconds are
+/// truncated and dates before the epoch are clamped to it.
+fn numeric_date(value: Option<&JsonValue>) -> Result<Option<u64>, TokenError> {
+    match value {
+        None => Ok(None),
+        Some(&JsonValue::U64(seconds)) => Ok(Some(seconds)),
+        Some(&JsonValue::I64(seconds)) => Ok(Some(if seconds < 0 { 0 } else { seconds as u64 })),
+        Some(&JsonValue::F64(seconds)) if seconds.is_finite() => Ok(Some(if seconds < 0.0 { 0 } else { seconds as u64 })),
+        Some(_) => Err(TokenError::Malformed)
+    }
+}
+
+fn strings(value: Option<&JsonValue>) -> Vec<String> {
+    match value.and_then(|value| value.as_array()) {
+        Some(values) => values.iter().filter_map(|v| v.as_str()).map(|v| v.to_string()).collect(),
+        None => vec![]
+    }
+}
+
+fn decode_object(part: &str) -> Result<json::Object, TokenError> {
+    let bytes = try!(part.from_base64().map_err(|_| TokenError::Malformed));
+    match serde_json::from_slice::<JsonValue>(&bytes) {
+        Ok(JsonValue::Object(object)) => Ok(object),
+        _ => Err(TokenError::Malformed)
+    }
+}
+
+fn decode_pem(pem: &str) -> Result<(String, Vec<u8>), KeyError> {
+    let mut label = None;
+    let mut body = String::new();
+    for line in pem.lines().map(|line| line.trim()) {
+        if line.starts_with("-----BEGIN ") && line.ends_with("-----") {
+            label = Some(line[11..line.len() - 5].to_string());
+        } else if line.starts_with("-----END ") {
+            break;
+        } else if label.is_some() {
+            body.push_str(line);
+        }
+    }
+
+    match label {
+        Some(label) => match body.from_base64() {
+            Ok(der) => Ok((label, der)),
+            Err(_) => Err(KeyError::Pem("PEM body is not valid base64".to_string()))
+        },
+        None => Err(KeyError::Pem("No PEM document found".to_string()))
+    }
+}
+
+/// Extracts the `RSAPublicKey` from a `SubjectPublicKeyInfo` structure
+fn spki_to_rsa_public_key(der: &[u8]) -> Option<Vec<u8>> {
+    der_element(der, 0x30)
+        .and_then(|(spki, _)| der_element(spki, 0x30))
+        .and_then(|(_algorithm, rest)| der_element(rest, 0x03))
+        .and_then(|(bits, _)| match bits.split_first() {
+            Some((&0, key)) => Some(key.to_vec()),
+            _ => None
+        })
+}
+
+/// Splits a DER element with the given tag into its contents and the rest of the input
+fn der_element(input: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    if input.len() < 2 || input[0] != tag {
+        return None;
+    }
+
+    let (len, header_len) = if input[1] < 0x80 {
+        (input[1] as usize, 2)
+    } else {
+        let octets = (input[1] & 0x7f) as usize;
+        if octets == 0 || octets > 4 || input.len() < 2 + octets {
+            return None;
+        }
+        (input[2..2 + octets].iter().fold(0, |len, byte| (len << 8) | *byte as usize), 2 + octets)
+    };
+
+    if input.len() < header_len + len {
+        return None;
+    }
+
+    Some((&input[header_len..header_len + len], &input[header_len + len..]))
+}
@@ -0,0 +1,22 @@
+use framework;
+
+pub use framework::security::{Principal, PrincipalKey};
+
+pub mod basic;
+pub mod jwt;
+pub mod api_key;
+
+pub trait AuthExt {
+    fn principal(&self) -> Option<&Principal>;
+    fn set_principal(&mut self, principal: Principal);
+}
+
+impl<'a> AuthExt for framework::Client<'a> {
+    fn principal(&self) -> Option<&Principal> {
+        self.ext.get::<PrincipalKey>()
+    }
+
+    fn set_principal(&mut self, principal: Principal) {
+        self.ext.insert::<PrincipalKey>(principal);
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use typemap;
+
+use backend;
+use framework;
+use framework::security::PrincipalKey;
+use json::{JsonValue};
+use server::{header, method, status};
+
+/// Application extension key of the response cache
+pub struct ResponseCacheKey;
+
+impl typemap::Key for ResponseCacheKey {
+    type Value = ResponseCache;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Visibility {
+    Public,
+    Private
+}
+
+/// `Cache-Control` directives of an endpoint, see `CacheExt::cache`.
+///
+/// Responses of `GET` and `HEAD` requests are also kept in the application
+/// `ResponseCache` when `store_for` is set, unless they are private or
+/// `no-store`. Requests with credentials, i.e. an `Authorization` or a
+/// `Cookie` header or an authenticated principal, are neither stored nor
+/// served from the cache unless the endpoint is explicitly `public`.
+#[derive(Clone, Debug)]
+pub struct CacheControl {
+    visibility: Option<Visibility>,
+    max_age: Option<u32>,
+    s_maxage: Option<u32>,
+    no_store: bool,
+    vary: Vec<String>,
+    store_for: Option<Duration>
+}
+
+impl CacheControl {
+    pub fn new() -> CacheControl {
+        CacheControl {
+            visibility: None,
+            max_age: None,
+            s_maxage: None,
+            no_store: false,
+            vary: vec![],
+            store_for: None
+        }
+    }
+
+    pub fn build<F>(builder: F) -> CacheControl where F: FnOnce(&mut CacheControl) {
+        let mut cache = CacheControl::new();
+        builder(&mut cache);
+
+        cache
+    }
+
+    pub fn public(&mut self) {
+        self.visibility = Some(Visibility::Public);
+    }
+
+    pub fn private(&mut self) {
+        self.visibility = Some(Visibility::Private);
+    }
+
+    pub fn max_age(&mut self, seconds: u32) {
+        self.max_age = Some(seconds);
+    }
+
+    /// Lifetime in shared caches
+    pub fn s_maxage(&mut self, seconds: u32) {
+        self.s_maxage = Some(seconds);
+    }
+
+    pub fn no_store(&mut self) {
+        self.no_store = true;
+    }
+
+    /// Request headers the response depends on. They are added to `Vary` and
+    /// to the key of the stored responses.
+    pub fn vary(&mut self, headers: &[&str]) {
+        self.vary = headers.iter().map(|header| header.to_string()).collect();
+    }
+
+    /// Keeps the responses in the application `ResponseCache` for `ttl`
+    pub fn store_for(&mut self, ttl: Duration) {
+        self.store_for = Some(ttl);
+    }
+
+    pub fn directives(&self) -> Vec<header::CacheDirective> {
+        let mut directives = vec![];
+        match self.visibility {
+            Some(Visibility::Public) => directives.push(header::CacheDirective::Public),
+            Some(Visibility::Private) => directives.push(header::CacheDirective::Private),
+            None => ()
+        }
+        if self.no_store {
+            directives.push(header::CacheDirective::NoStore);
+        }
+        if let Some(seconds) = self.max_age {
+            directives.push(header::CacheDirective::MaxAge(seconds));
+        }
+        if let Some(seconds) = self.s_maxage {
+            directives.push(header::CacheDirective::SMaxAge(seconds));
+        }
+
+        directives
+    }
+
+    /// Sets `Cache-Control` unless the handler did, and adds the `Vary` headers
+    pub fn apply(&self, response: &mut backend::Response) {
+        let directives = self.directives();
+        if !directives.is_empty() && !response.headers.has::<header::CacheControl>() {
+            response.set_header(header::CacheControl(directives));
+        }
+        for name in self.vary.iter() {
+            response.add_vary(name);
+        }
+    }
+
+    fn stored_for(&self, client: &framework::Client) -> Option<Duration> {
+        let shared = self.visibility == Some(Visibility::Public) || !has_credentials(client);
+        if self.no_store || self.visibility == Some(Visibility::Private) || !shared {
+            None
+        } else {
+            self.store_for
+        }
+    }
+
+}
+
+impl framework::Around for CacheControl {
+    /// Runs the handler, or answers with the stored response when there is one
+    fn call<'a, 'n>(&self, mut client: framework::Client<'a>, params: &mut JsonValue, next: framework::Next<'n>) -> framework::client::ClientResult<'a> {
+        let app = client.app;
+        let cacheable = {
+            let method = client.request.method();
+            method == &method::Method::Get || method == &method::Method::Head
+        };
+        let store = match (self.stored_for(&client), app.ext.get::<ResponseCacheKey>()) {
+            (Some(ttl), Some(store)) if cacheable => Some((ttl, store)),
+            _ => None
+        };
+
+        let key = store.map(|_| Key::new(&client, &self.vary));
+        if let (Some(&(_, store)), Some(ref key)) = (store.as_ref(), key.as_ref()) {
+            if let Some(response) = store.get(key) {
+                client.response = response;
+                return Ok(client);
+            }
+        }
+
+        let mut client = try!(next.run(client, params));
+        self.apply(&mut client.response);
+
+        if let (Some((ttl, store)), Some(key)) = (store, key) {
+            try!(store.insert(key, &mut client.response, ttl).map_err(|err| error_response!(err)));
+        }
+
+        Ok(client)
+    }
+}
+
+/// The response may depend on who sent the request
+fn has_credentials(client: &framework::Client) -> bool {
+    let headers = client.request.headers();
+    headers.get_raw("Authorization").is_some() || headers.get_raw("Cookie").is_some() ||
+        client.ext.contains::<PrincipalKey>()
+}
+
+pub trait CacheExt {
+    /// Declares the `Cache-Control` directives of the responses, which can
+    /// also be kept in the application `ResponseCache`
+    fn cache<F>(&mut self, builder: F) where F: FnOnce(&mut CacheControl);
+}
+
+impl CacheExt for framework::Endpoint {
+    fn cache<F>(&mut self, builder: F) where F: FnOnce(&mut CacheControl) {
+        self.around_handler(CacheControl::build(builder));
+    }
+}
+
+/// Identifies a stored response
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    /// Route template and API version of the endpoint, APIs versioned by the
+    /// `Accept` header may serve the same path
+    pub route: Option<String>,
+    pub version: Option<String>,
+    /// The requested media type
+    pub media: String,
+    /// Values of the `Vary` request headers
+    pub vary: Vec<(String, Option<String>)>
+}
+
+impl Key {
+    pub fn new(client: &framework::Client, vary: &[String]) -> Key {
+        let req = &*client.request;
+        let route = req.ext().get::<framework::MatchedRoute>();
+        let media = client.media;
+        Key {
+            method: req.method().to_string(),
+            path: format!("/{}", req.url().path().join("/")),
+            query: req.url().query().map(|query| query.to_string()),
+            route: route.map(|route| route.route.clone()),
+            version: route.and_then(|route| route.version.clone()),
+            media: format!("{}.{:?}.{:?}+{:?}", media.vendor, media.version, media.param, media.format),
+            vary: vary.iter().map(|name| {
+                let value = req.headers().get_raw(name).map(|values| {
+                    values.iter().map(|value| String::from_utf8_lossy(value).into_owned()).collect::<Vec<String>>().join(",")
+                });
+                (name.to_lowercase(), value)
+            }).collect()
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.path.len() + self.query.as_ref().map_or(0, |query| query.len())
+    }
+}
+
+struct Entry {
+    status: status::StatusCode,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    stored_at: Instant,
+    ttl: Duration,
+    size: usize,
+    /// Position in the LRU order
+    tick: u64
+}
+
+struct Entries {
+    map: HashMap<Key, Entry>,
+    /// Least recently used first
+    order: BTreeMap<u64, Key>,
+    tick: u64,
+    size: usize
+}
+
+impl Entries {
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.map.remove(key) {
+            self.order.remove(&entry.tick);
+            self.size -= entry.size;
+        }
+    }
+
+    fn remove_where<F>(&mut self, predicate: F) -> usize where F: Fn(&Key) -> bool {
+        let keys: Vec<Key> = self.map.keys().filter(|key| predicate(key)).cloned().collect();
+        for key in keys.iter() {
+            self.remove(key);
+        }
+
+        keys.len()
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+/// In-process store of full responses, evicting the least recently used
+/// ones when their size goes over `max_size` (64 MiB by default).
+///
+/// Sizes are approximate: bodies, headers and keys are counted.
+pub struct ResponseCache {
+    max_size: usize,
+    entries: Mutex<Entries>
+}
+
+impl ResponseCache {
+    pub fn new() -> ResponseCache {
+        ResponseCache {
+            max_size: 64 * 1024 * 1024,
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+                size: 0
+            })
+        }
+    }
+
+    pub fn build<F>(builder: F) -> ResponseCache where F: FnOnce(&mut ResponseCache) {
+        let mut cache = ResponseCache::new();
+        builder(&mut cache);
+
+        cache
+    }
+
+    /// Size limit in bytes
+    pub fn max_size(&mut self, bytes: usize) {
+        self.max_size = bytes;
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns a copy of the stored response with an `Age` header
+    pub fn get(&self, key: &Key) -> Option<backend::Response> {
+        let mut guard = self.lock();
+        let entries = &mut *guard;
+
+        let expired = match entries.map.get(key) {
+            Some(entry) => entry.stored_at.elapsed() >= entry.ttl,
+            None => return None
+        };
+        if expired {
+            entries.remove(key);
+            return None;
+        }
+
+        let tick = entries.next_tick();
+        let entry = entries.map.get_mut(key).unwrap();
+        entries.order.remove(&entry.tick);
+        entries.order.insert(tick, key.clone());
+        entry.tick = tick;
+
+        let mut response = backend::Response::new(entry.status);
+        for &(ref name, ref value) in entry.headers.iter() {
+            response.headers.set_raw(name.clone(), vec![value.clone().into_bytes()]);
+        }
+        response.headers.set_raw("Age", vec![entry.stored_at.elapsed().as_secs().to_string().into_bytes()]);
+        response.replace_body(Box::new(entry.body.clone()));
+
+        Some(response)
+    }
+
+    /// Stores a `200 OK` response without cookies, the body is buffered and
+    /// put back into `response`
+    pub fn insert(&self, key: Key, response: &mut backend::Response, ttl: Duration) -> ::std::io::Result<()> {
+        if response.status != status::StatusCode::Ok || response.headers.get_raw("Set-Cookie").is_some() {
+            return Ok(());
+        }
+
+        let body = try!(response.take_body_bytes()).unwrap_or(vec![]);
+        response.replace_body(Box::new(body.clone()));
+
+        let headers: Vec<(String, String)> = response.headers.iter()
+            .map(|header| (header.name().to_string(), header.value_string()))
+            .collect();
+        let size = key.size() + body.len() +
+            headers.iter().map(|&(ref name, ref value)| name.len() + value.len()).sum::<usize>();
+        if size > self.max_size {
+            return Ok(());
+        }
+
+        let mut entries = self.lock();
+        entries.remove(&key);
+        while entries.size + size > self.max_size {
+            let oldest = match entries.order.values().next() {
+                Some(oldest) => oldest.clone(),
+                None => break
+            };
+            entries.remove(&oldest);
+        }
+
+        let tick = entries.next_tick();
+        entries.order.insert(tick, key.clone());
+        entries.size += size;
+        entries.map.insert(key, Entry {
+            status: response.status,
+            headers: headers,
+            body: body,
+            stored_at: Instant::now(),
+            ttl: ttl,
+            size: size,
+            tick: tick
+        });
+
+        Ok(())
+    }
+
+    /// Removes the responses of `path` whatever their query and method,
+    /// returns how many were removed
+    pub fn invalidate(&self, path: &str) -> usize {
+        self.lock().remove_where(|key| key.path == path)
+    }
+
+    /// Removes the responses of the paths starting with `prefix`
+    pub fn invalidate_prefix(&self, prefix: &str) -> usize {
+        self.lock().remove_where(|key| key.path.starts_with(prefix))
+    }
+
+    pub fn clear(&self) {
+        self.lock().remove_where(|_| true);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate size of the stored responses in bytes
+    pub fn size(&self) -> usize {
+        self.lock().size
+    }
+}
+
+/// Stores the responses of the endpoints declaring `store_for`
+pub fn enable(app: &mut framework::Application, cache: ResponseCache) {
+    app.ext.insert::<ResponseCacheKey>(cache);
+}
+
+#[cfg(test)]
+fn test_key(path: &str) -> Key {
+    Key {
+        method: "GET".to_string(),
+        path: path.to_string(),
+        query: None,
+        route: None,
+        version: None,
+        media: "default".to_string(),
+        vary: vec![]
+    }
+}
+
+#[test]
+fn it_evicts_least_recently_used_responses() {
+    let cache = ResponseCache::build(|cache| cache.max_size(100));
+    let ttl = Duration::from_secs(60);
+    for path in ["/a", "/b", "/c"].iter() {
+        let mut response = backend::Response::from(status::StatusCode::Ok, Box::new(vec![0u8; 30]));
+        cache.insert(test_key(path), &mut response, ttl).unwrap();
+    }
+    assert_eq!(cache.len(), 3);
+
+    // "/a" becomes the most recently used, "/b" is evicted next
+    assert!(cache.get(&test_key("/a")).is_some());
+    let mut response = backend::Response::from(status::StatusCode::Ok, Box::new(vec![0u8; 30]));
+    cache.insert(test_key("/d"), &mut response, ttl).unwrap();
+
+    assert!(cache.get(&test_key("/b")).is_none());
+    assert!(cache.get(&test_key("/a")).is_some());
+    assert!(cache.get(&test_key("/c")).is_some());
+    assert!(cache.get(&test_key("/d")).is_some());
+    assert!(cache.size() <= 100);
+}
+
+#[test]
+fn it_formats_cache_control_directives() {
+    let cache = CacheControl::build(|cache| {
+        cache.public();
+        cache.max_age(60);
+        cache.s_maxage(300);
+    });
+    let directives: Vec<String> = cache.directives().iter().map(|directive| directive.to_string()).collect();
+    assert_eq!(directives.join(", "), "public, max-age=60, s-maxage=300");
+}
@@ -0,0 +1,242 @@
+use std::io::{self, Write};
+use flate2;
+use flate2::write::{GzEncoder, ZlibEncoder};
+#[cfg(feature = "brotli")]
+use brotli;
+
+use backend;
+use framework::{self, CallbackFilter, EndpointMatcher, Nesting};
+use json::{JsonValue};
+use server::{header, status};
+
+/// Content codings the responses can be compressed with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Gzip,
+    /// The zlib format, as HTTP defines `deflate`
+    Deflate,
+    #[cfg(feature = "brotli")]
+    Brotli
+}
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => "br"
+        }
+    }
+
+    /// `level` goes from 0 (fastest) to 9 (smallest)
+    pub fn encode(&self, bytes: &[u8], level: u32) -> io::Result<Vec<u8>> {
+        match *self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+                try!(encoder.write_all(bytes));
+                encoder.finish()
+            },
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::new(level));
+                try!(encoder.write_all(bytes));
+                encoder.finish()
+            },
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, level, 22);
+                try!(encoder.write_all(bytes));
+                Ok(encoder.into_inner())
+            }
+        }
+    }
+}
+
+/// Picks the encoding the client prefers among `supported`, which is ordered
+/// by the preference of the server
+pub fn negotiate(accept_encoding: &str, supported: &[Encoding]) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f32)> = None;
+
+    for encoding in supported.iter() {
+        let mut quality = None;
+        for item in accept_encoding.split(',') {
+            let mut parts = item.split(';');
+            let name = parts.next().unwrap_or("").trim().to_lowercase();
+            let q = parts
+                .filter_map(|param| {
+                    let param = param.trim();
+                    if param.starts_with("q=") { param[2..].trim().parse::<f32>().ok() } else { None }
+                })
+                .next()
+                .unwrap_or(1.0);
+
+            if name == encoding.name() {
+                quality = Some(q);
+                break;
+            } else if name == "*" && quality.is_none() {
+                quality = Some(q);
+            }
+        }
+
+        match quality {
+            Some(q) if q > 0.0 && best.map_or(true, |(_, best_q)| q > best_q) => best = Some((*encoding, q)),
+            _ => ()
+        }
+    }
+
+    best.map(|(encoding, _)| encoding)
+}
+
+/// Response compression settings.
+///
+/// Bodies smaller than `min_size` (1024 bytes by default), of content types
+/// missing from the allowlist, or already carrying a `Content-Encoding` are
+/// sent as is.
+pub struct Compression {
+    encodings: Vec<Encoding>,
+    level: u32,
+    min_size: usize,
+    content_types: Vec<String>,
+    filter: Option<CallbackFilter>
+}
+
+impl Compression {
+    pub fn new() -> Compression {
+        Compression {
+            encodings: default_encodings(),
+            level: 6,
+            min_size: 1024,
+            content_types: [
+                "text/*", "application/json", "application/problem+json", "application/javascript",
+                "application/xml", "image/svg+xml"
+            ].iter().map(|content_type| content_type.to_string()).collect(),
+            filter: None
+        }
+    }
+
+    pub fn build<F>(builder: F) -> Compression where F: FnOnce(&mut Compression) {
+        let mut compression = Compression::new();
+        builder(&mut compression);
+
+        compression
+    }
+
+    /// Supported encodings, the preferred first
+    pub fn encodings(&mut self, encodings: Vec<Encoding>) {
+        self.encodings = encodings;
+    }
+
+    pub fn level(&mut self, level: u32) {
+        self.level = level;
+    }
+
+    pub fn min_size(&mut self, bytes: usize) {
+        self.min_size = bytes;
+    }
+
+    /// Compressible content types, `type/*` matches a whole type
+    pub fn content_types(&mut self, content_types: &[&str]) {
+        self.content_types = content_types.iter().map(|content_type| content_type.to_lowercase()).collect();
+    }
+
+    /// Leaves the responses of some endpoints alone, e.g. already compressed files
+    pub fn except(&mut self, matchers: Vec<EndpointMatcher>) {
+        self.filter = Some(CallbackFilter::except(matchers));
+    }
+
+    fn allows_content_type(&self, response: &backend::Response) -> bool {
+        let content_type = match response.headers.get::<header::ContentType>() {
+            Some(&header::ContentType(ref mime)) => {
+                let &::server::mime::Mime(ref top, ref sub, _) = mime;
+                format!("{}/{}", top, sub).to_lowercase()
+            },
+            None => return false
+        };
+
+        self.content_types.iter().any(|allowed| {
+            if allowed.ends_with("/*") {
+                content_type.starts_with(&allowed[..allowed.len() - 1])
+            } else {
+                &content_type == allowed
+            }
+        })
+    }
+
+    /// Compresses the body of `response` with an encoding accepted by the client
+    pub fn compress(&self, accept_encoding: Option<&str>, response: &mut backend::Response) -> io::Result<()> {
+        if response.body.is_none() || response.headers.get_raw("Content-Encoding").is_some() ||
+           response.status == status::StatusCode::NoContent || response.status == status::StatusCode::NotModified ||
+           !self.allows_content_type(response) {
+            return Ok(());
+        }
+
+        response.add_vary("Accept-Encoding");
+
+        let encoding = match accept_encoding.and_then(|accept| negotiate(accept, &self.encodings)) {
+            Some(encoding) => encoding,
+            None => return Ok(())
+        };
+
+        let bytes = match try!(response.take_body_bytes()) {
+            Some(bytes) => bytes,
+            None => return Ok(())
+        };
+
+        if bytes.len() < self.min_size {
+            response.replace_body(Box::new(bytes));
+            return Ok(());
+        }
+
+        let encoded = try!(encoding.encode(&bytes, self.level));
+        response.headers.remove_raw("Content-Length");
+        response.headers.set_raw("Content-Encoding", vec![encoding.name().as_bytes().to_vec()]);
+        response.replace_body(Box::new(encoded));
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "brotli")]
+fn default_encodings() -> Vec<Encoding> {
+    vec![Encoding::Brotli, Encoding::Gzip, Encoding::Deflate]
+}
+
+#[cfg(not(feature = "brotli"))]
+fn default_encodings() -> Vec<Encoding> {
+    vec![Encoding::Gzip, Encoding::Deflate]
+}
+
+impl framework::Around for Compression {
+    fn call<'a, 'n>(&self, client: framework::Client<'a>, params: &mut JsonValue, next: framework::Next<'n>) -> framework::client::ClientResult<'a> {
+        if self.filter.as_ref().map_or(false, |filter| !filter.allows(client.endpoint)) {
+            return next.run(client, params);
+        }
+
+        let accept_encoding = client.request.headers().get_raw("Accept-Encoding")
+            .map(|values| values.iter()
+                .filter_map(|value| String::from_utf8(value.clone()).ok())
+                .collect::<Vec<String>>()
+                .join(","));
+
+        let mut client = try!(next.run(client, params));
+        try!(self.compress(accept_encoding.as_ref().map(|accept| &accept[..]), &mut client.response)
+            .map_err(|err| error_response!(err)));
+
+        Ok(client)
+    }
+}
+
+/// Compresses the responses of the endpoints of `node`
+pub fn enable<N: Nesting>(node: &mut N, compression: Compression) {
+    node.around_middleware(compression);
+}
+
+#[test]
+fn it_negotiates_encodings() {
+    let supported = [Encoding::Gzip, Encoding::Deflate];
+    assert_eq!(negotiate("gzip, deflate", &supported), Some(Encoding::Gzip));
+    assert_eq!(negotiate("deflate, gzip;q=0.5", &supported), Some(Encoding::Deflate));
+    assert_eq!(negotiate("*;q=0.1, deflate;q=0", &supported), Some(Encoding::Gzip));
+    assert_eq!(negotiate("gzip;q=0, identity", &supported), None);
+    assert_eq!(negotiate("br", &supported), None);
+}
@@ -0,0 +1,192 @@
+use ring::digest;
+use typemap;
+
+use backend;
+use errors;
+use framework::{self, Nesting};
+use json::{JsonValue};
+use server::{header, method, status};
+
+/// Conditional GET settings, see `enable`.
+///
+/// Successful `GET` and `HEAD` responses get a weak `ETag` computed over their
+/// body unless the handler set one. Requests whose `If-None-Match` matches it,
+/// or whose `If-Modified-Since` is not older than the `Last-Modified` set by
+/// the handler, are answered with `304 Not Modified`.
+#[derive(Clone, Debug)]
+pub struct ConditionalGet {
+    enabled: bool,
+    auto_etag: bool
+}
+
+impl ConditionalGet {
+    pub fn new() -> ConditionalGet {
+        ConditionalGet {
+            enabled: true,
+            auto_etag: true
+        }
+    }
+
+    /// Turns conditional requests off for a subtree
+    pub fn disabled() -> ConditionalGet {
+        ConditionalGet {
+            enabled: false,
+            auto_etag: false
+        }
+    }
+
+    pub fn build<F>(builder: F) -> ConditionalGet where F: FnOnce(&mut ConditionalGet) {
+        let mut conditional = ConditionalGet::new();
+        builder(&mut conditional);
+
+        conditional
+    }
+
+    /// Only use the `ETag`s set by the handlers
+    pub fn auto_etag(&mut self, auto_etag: bool) {
+        self.auto_etag = auto_etag;
+    }
+
+    pub fn apply(&self, req: &backend::Request, response: &mut backend::Response) {
+        if !self.enabled || response.status != status::StatusCode::Ok ||
+           (req.method() != &method::Method::Get && req.method() != &method::Method::Head) {
+            return;
+        }
+
+        if self.auto_etag && !response.headers.has::<header::ETag>() {
+            if let Ok(Some(bytes)) = response.take_body_bytes() {
+                response.set_header(header::ETag(weak_etag(&bytes)));
+                response.replace_body(Box::new(bytes));
+            }
+        }
+
+        if is_not_modified(req, response) {
+            response.status = status::StatusCode::NotModified;
+            response.body = None;
+            response.headers.remove::<header::ContentType>();
+            response.headers.remove::<header::ContentLength>();
+            response.headers.remove_raw("Content-Encoding");
+        }
+    }
+}
+
+/// Marks the responses a `ConditionalGet` of an inner node already handled
+struct Handled;
+
+impl typemap::Key for Handled {
+    type Value = ();
+}
+
+impl framework::Around for ConditionalGet {
+    fn call<'a, 'n>(&self, client: framework::Client<'a>, params: &mut JsonValue, next: framework::Next<'n>) -> framework::client::ClientResult<'a> {
+        let mut client = try!(next.run(client, params));
+        if !client.ext.contains::<Handled>() {
+            client.ext.insert::<Handled>(());
+            self.apply(client.request, &mut client.response);
+        }
+
+        Ok(client)
+    }
+}
+
+/// Answers conditional `GET` requests of the subtree, the settings of the
+/// innermost node apply
+pub fn enable<N: Nesting>(node: &mut N, conditional: ConditionalGet) {
+    node.around_middleware(conditional);
+}
+
+/// Returns the current version of a resource, `None` when it doesn't exist
+pub type VersionFn = Box<for<'a> Fn(&mut framework::Client<'a>, &JsonValue) -> backend::HandleResult<Option<String>> + 'static + Sync + Send>;
+
+struct IfMatch {
+    version: VersionFn
+}
+
+impl framework::Around for IfMatch {
+    fn call<'a, 'n>(&self, mut client: framework::Client<'a>, params: &mut JsonValue, next: framework::Next<'n>) -> framework::client::ClientResult<'a> {
+        let current = try!((self.version)(&mut client, params));
+        try!(check_if_match(client.request, current.as_ref().map(|version| &version[..])));
+        next.run(client, params)
+    }
+}
+
+pub trait IfMatchExt {
+    /// Requires requests to send an `If-Match` header matching the current
+    /// version of the resource returned by `version`. It is checked after the
+    /// `after_validation` callbacks, right before the handler.
+    ///
+    /// The comparison is strong, so the weak `ETag`s computed by
+    /// `ConditionalGet` never match: handlers of resources updated this way
+    /// should send `EntityTag::strong(version)` themselves. The version may be
+    /// given with or without the surrounding quotes.
+    fn requires_if_match<F: 'static>(&mut self, version: F)
+    where F: for<'a> Fn(&mut framework::Client<'a>, &JsonValue) -> backend::HandleResult<Option<String>> + Send+Sync;
+}
+
+impl IfMatchExt for framework::Endpoint {
+    fn requires_if_match<F: 'static>(&mut self, version: F)
+    where F: for<'a> Fn(&mut framework::Client<'a>, &JsonValue) -> backend::HandleResult<Option<String>> + Send+Sync {
+        self.around_handler(IfMatch { version: Box::new(version) });
+    }
+}
+
+/// Checks `If-Match` against the current version of the resource using the
+/// strong comparison, the version may be quoted
+pub fn check_if_match(req: &backend::Request, current: Option<&str>) -> backend::HandleSuccessResult {
+    let matches = match req.headers().get::<header::IfMatch>() {
+        Some(&header::IfMatch::Any) => current.is_some(),
+        Some(&header::IfMatch::Items(ref tags)) => match current {
+            Some(version) => {
+                let current = header::EntityTag::strong(unquote(version).to_string());
+                tags.iter().any(|tag| tag.strong_eq(&current))
+            },
+            None => false
+        },
+        None => return Err(error_response!(errors::PreconditionRequired))
+    };
+
+    if matches { Ok(()) } else { Err(error_response!(errors::PreconditionFailed)) }
+}
+
+fn unquote(version: &str) -> &str {
+    if version.len() >= 2 && version.starts_with('"') && version.ends_with('"') {
+        &version[1..version.len() - 1]
+    } else {
+        version
+    }
+}
+
+/// Weak entity tag of a body
+pub fn weak_etag(bytes: &[u8]) -> header::EntityTag {
+    let hash = digest::digest(&digest::SHA256, bytes);
+    let hex: Vec<String> = hash.as_ref()[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    header::EntityTag::weak(hex.concat())
+}
+
+fn is_not_modified(req: &backend::Request, response: &backend::Response) -> bool {
+    // If-Modified-Since is ignored when If-None-Match is present
+    match req.headers().get::<header::IfNoneMatch>() {
+        Some(&header::IfNoneMatch::Any) => return response.headers.has::<header::ETag>(),
+        Some(&header::IfNoneMatch::Items(ref tags)) => {
+            return match response.headers.get::<header::ETag>() {
+                Some(&header::ETag(ref etag)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+                None => false
+            };
+        },
+        None => ()
+    }
+
+    match (req.headers().get::<header::IfModifiedSince>(), response.headers.get::<header::LastModified>()) {
+        (Some(&header::IfModifiedSince(since)), Some(&header::LastModified(modified))) => modified <= since,
+        _ => false
+    }
+}
+
+#[test]
+fn it_computes_stable_weak_etags() {
+    let etag = weak_etag(b"{\"id\":1}");
+    assert!(etag.weak);
+    assert_eq!(etag.tag().len(), 32);
+    assert_eq!(etag, weak_etag(b"{\"id\":1}"));
+    assert!(etag != weak_etag(b"{\"id\":2}"));
+}
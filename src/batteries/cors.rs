@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use backend;
+use errors;
+use framework::{self, Nesting};
+use json::{JsonValue};
+use server::{method, status};
+
+/// An origin allowed to make cross-origin requests
+pub enum AllowedOrigin {
+    Any,
+    Exact(String),
+    /// A pattern with a single `*`, e.g. `https://*.example.com`
+    Wildcard(String),
+    Predicate(Box<Fn(&str) -> bool + Send + Sync>)
+}
+
+impl AllowedOrigin {
+    pub fn matches(&self, origin: &str) -> bool {
+        match self {
+            &AllowedOrigin::Any => true,
+            &AllowedOrigin::Exact(ref allowed) => allowed == origin,
+            &AllowedOrigin::Wildcard(ref pattern) => {
+                match pattern.find('*') {
+                    Some(pos) => {
+                        let (prefix, suffix) = (&pattern[..pos], &pattern[pos + 1..]);
+                        origin.len() >= prefix.len() + suffix.len() &&
+                            origin.starts_with(prefix) && origin.ends_with(suffix)
+                    },
+                    None => pattern == origin
+                }
+            },
+            &AllowedOrigin::Predicate(ref predicate) => predicate(origin)
+        }
+    }
+}
+
+/// Cross-Origin Resource Sharing settings.
+///
+/// Preflight requests are answered with the methods of the routes matching
+/// the requested path, limited to `methods` when they are set. When no
+/// headers are configured the requested ones are allowed.
+pub struct Cors {
+    origins: Vec<AllowedOrigin>,
+    methods: Option<Vec<method::Method>>,
+    headers: Option<Vec<String>>,
+    exposed_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<u32>
+}
+
+impl Cors {
+    pub fn new() -> Cors {
+        Cors {
+            origins: vec![],
+            methods: None,
+            headers: None,
+            exposed_headers: vec![],
+            credentials: false,
+            max_age: None
+        }
+    }
+
+    pub fn build<F>(builder: F) -> Cors where F: FnOnce(&mut Cors) {
+        let mut cors = Cors::new();
+        builder(&mut cors);
+
+        cors
+    }
+
+    pub fn allow_any_origin(&mut self) {
+        self.origins.push(AllowedOrigin::Any);
+    }
+
+    pub fn allow_origin(&mut self, origin: &str) {
+        self.origins.push(AllowedOrigin::Exact(origin.to_string()));
+    }
+
+    pub fn allow_origin_wildcard(&mut self, pattern: &str) {
+        self.origins.push(AllowedOrigin::Wildcard(pattern.to_string()));
+    }
+
+    pub fn allow_origin_if<F: 'static>(&mut self, predicate: F) where F: Fn(&str) -> bool + Send+Sync {
+        self.origins.push(AllowedOrigin::Predicate(Box::new(predicate)));
+    }
+
+    pub fn allow_methods(&mut self, methods: Vec<method::Method>) {
+        self.methods = Some(methods);
+    }
+
+    pub fn allow_headers(&mut self, headers: &[&str]) {
+        self.headers = Some(headers.iter().map(|header| header.to_string()).collect());
+    }
+
+    pub fn expose_headers(&mut self, headers: &[&str]) {
+        self.exposed_headers = headers.iter().map(|header| header.to_string()).collect();
+    }
+
+    /// Allows cookies and `Authorization` headers in cross-origin requests.
+    /// It can't be combined with `allow_any_origin`, `enable` panics on such
+    /// settings instead of letting every site make credentialed requests.
+    pub fn allow_credentials(&mut self, credentials: bool) {
+        self.credentials = credentials;
+    }
+
+    /// Seconds the results of a preflight request can be cached
+    pub fn max_age(&mut self, seconds: u32) {
+        self.max_age = Some(seconds);
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.origins.iter().any(|allowed| match allowed { &AllowedOrigin::Any => true, _ => false })
+    }
+
+    /// The `Access-Control-Allow-Origin` value for the request origin, if it is allowed
+    pub fn allowed_origin(&self, origin: &str) -> Option<String> {
+        if !self.origins.iter().any(|allowed| allowed.matches(origin)) {
+            return None;
+        }
+
+        if self.allows_any_origin() {
+            Some("*".to_string())
+        } else {
+            Some(origin.to_string())
+        }
+    }
+
+    /// Responses depend on the request origin unless any origin is allowed,
+    /// including the ones without CORS headers
+    fn add_vary(&self, response: &mut backend::Response) {
+        if !self.allows_any_origin() {
+            response.add_vary("Origin");
+        }
+    }
+
+    fn set_origin_headers(&self, response: &mut backend::Response, allowed_origin: String) {
+        response.headers.set_raw("Access-Control-Allow-Origin", vec![allowed_origin.into_bytes()]);
+        if self.credentials {
+            response.headers.set_raw("Access-Control-Allow-Credentials", vec![b"true".to_vec()]);
+        }
+    }
+
+    /// Adds the headers of an actual (not preflight) cross-origin request
+    pub fn apply(&self, origin: &str, response: &mut backend::Response) {
+        if let Some(allowed_origin) = self.allowed_origin(origin) {
+            self.set_origin_headers(response, allowed_origin);
+            if !self.exposed_headers.is_empty() {
+                response.headers.set_raw("Access-Control-Expose-Headers", vec![self.exposed_headers.join(", ").into_bytes()]);
+            }
+        }
+    }
+
+    /// Answers a preflight request for a path served by `route_methods`.
+    ///
+    /// Origins that are not allowed get neither the CORS headers nor the
+    /// methods of the path.
+    pub fn preflight(&self, req: &backend::Request, route_methods: Vec<method::Method>) -> backend::Response {
+        let mut response = backend::Response::new(status::StatusCode::NoContent);
+        self.add_vary(&mut response);
+
+        let methods: Vec<method::Method> = match self.methods {
+            Some(ref allowed) => route_methods.into_iter().filter(|m| allowed.contains(m)).collect(),
+            None => route_methods
+        };
+        let methods: Vec<String> = methods.iter().map(|m| m.to_string()).collect();
+
+        let allowed_origin = match header_value(req, "Origin") {
+            Some(origin) => match self.allowed_origin(&origin) {
+                Some(allowed_origin) => allowed_origin,
+                None => return response
+            },
+            // A plain `OPTIONS` request
+            None => {
+                response.headers.set_raw("Allow", vec![methods.join(", ").into_bytes()]);
+                return response;
+            }
+        };
+
+        response.headers.set_raw("Allow", vec![methods.join(", ").into_bytes()]);
+        self.set_origin_headers(&mut response, allowed_origin);
+        response.headers.set_raw("Access-Control-Allow-Methods", vec![methods.join(", ").into_bytes()]);
+
+        let headers = match self.headers {
+            Some(ref headers) => Some(headers.join(", ")),
+            None => header_value(req, "Access-Control-Request-Headers")
+        };
+        if let Some(headers) = headers {
+            response.headers.set_raw("Access-Control-Allow-Headers", vec![headers.into_bytes()]);
+        }
+
+        if let Some(max_age) = self.max_age {
+            response.headers.set_raw("Access-Control-Max-Age", vec![max_age.to_string().into_bytes()]);
+        }
+
+        response
+    }
+}
+
+fn header_value(req: &backend::Request, name: &str) -> Option<String> {
+    req.headers().get_raw(name)
+        .and_then(|values| values.first())
+        .and_then(|value| String::from_utf8(value.clone()).ok())
+}
+
+/// Answers `OPTIONS` requests for the paths of the node it is mounted on
+pub struct Preflight {
+    cors: Arc<Cors>
+}
+
+impl framework::ApiHandler for Preflight {
+    fn api_call<'a, 'r>(&'a self,
+        rest_path: &str,
+        _params: &mut JsonValue,
+        req: &'r mut (backend::Request + 'r),
+        info: &mut framework::CallInfo<'a>) -> backend::HandleResult<backend::Response> {
+
+        if req.method() != &method::Method::Options {
+            return Err(error_response!(errors::NotMatch));
+        }
+
+        let mut methods = vec![];
+        if let Some(node) = info.parents.last() {
+            for handler in node.get_handlers().iter() {
+                handler.route_methods(rest_path, &mut methods);
+            }
+        }
+
+        // Explicit OPTIONS endpoints take precedence
+        if methods.is_empty() || methods.contains(&method::Method::Options) {
+            return Err(error_response!(errors::NotMatch));
+        }
+
+        methods.push(method::Method::Options);
+        Ok(self.cors.preflight(req, methods))
+    }
+}
+
+/// Enables CORS for every endpoint of `node`. The headers are added to the
+/// error responses as well. When nested nodes enable CORS, the innermost
+/// one that allows the origin sets the headers.
+///
+/// Panics when `cors` allows any origin together with credentials.
+pub fn enable<N: Nesting>(node: &mut N, cors: Cors) {
+    if cors.allows_any_origin() && cors.credentials {
+        panic!("CORS credentials can't be allowed for any origin, list the allowed origins instead");
+    }
+
+    let cors = Arc::new(cors);
+    let hook_cors = cors.clone();
+    node.on_response(move |req, response| {
+        // A preflight response or the response of an inner node
+        if response.headers.get_raw("Access-Control-Allow-Origin").is_some() {
+            return;
+        }
+        hook_cors.add_vary(response);
+        if let Some(origin) = header_value(req, "Origin") {
+            hook_cors.apply(&origin, response);
+        }
+    });
+    node.mount(Preflight { cors: cors });
+}
@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use jsonway;
+
+use framework::{self, Nesting};
+use json::{JsonValue, ToJson};
+use server::status;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Up,
+    Down
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Status::Up => "up",
+            Status::Down => "down"
+        }
+    }
+}
+
+/// What a check reports
+#[derive(Clone, Debug)]
+pub struct CheckResult {
+    pub status: Status,
+    pub details: Option<JsonValue>
+}
+
+impl CheckResult {
+    pub fn up() -> CheckResult {
+        CheckResult { status: Status::Up, details: None }
+    }
+
+    pub fn down(details: JsonValue) -> CheckResult {
+        CheckResult { status: Status::Down, details: Some(details) }
+    }
+
+    pub fn with_details(status: Status, details: JsonValue) -> CheckResult {
+        CheckResult { status: status, details: Some(details) }
+    }
+}
+
+/// Liveness checks tell whether the service should be restarted, readiness
+/// checks whether it can receive traffic
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Liveness,
+    Readiness
+}
+
+pub type CheckFn = Arc<Fn() -> CheckResult + 'static + Send + Sync>;
+
+/// A run of a check, shared by the probes that come while it is in progress
+struct Run {
+    result: Mutex<Option<CheckResult>>,
+    done: Condvar
+}
+
+impl Run {
+    /// The result, or `None` when the check doesn't finish within `timeout`
+    fn wait(&self, timeout: Duration) -> Option<CheckResult> {
+        let started = Instant::now();
+        let mut result = lock(&self.result);
+        while result.is_none() {
+            let elapsed = started.elapsed();
+            if elapsed >= timeout {
+                return None;
+            }
+            result = self.done.wait_timeout(result, timeout - elapsed)
+                .unwrap_or_else(|poisoned| poisoned.into_inner()).0;
+        }
+
+        result.clone()
+    }
+}
+
+struct Check {
+    name: String,
+    kind: Kind,
+    timeout: Option<Duration>,
+    check: CheckFn,
+    running: Arc<Mutex<Option<Arc<Run>>>>
+}
+
+impl Check {
+    /// Starts the check in its own thread unless it is still running
+    fn start(&self) -> Arc<Run> {
+        let mut running = lock(&self.running);
+        if let Some(ref run) = *running {
+            return run.clone();
+        }
+
+        let run = Arc::new(Run { result: Mutex::new(None), done: Condvar::new() });
+        *running = Some(run.clone());
+
+        let check = self.check.clone();
+        let slot = self.running.clone();
+        let shared = run.clone();
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| check()))
+                .unwrap_or_else(|_| CheckResult::down("Check failed".to_json()));
+            *lock(&slot) = None;
+            *lock(&shared.result) = Some(result);
+            shared.done.notify_all();
+        });
+
+        run
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Results of a run of the checks
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub status: Status,
+    pub checks: BTreeMap<String, CheckResult>
+}
+
+impl ToJson for Report {
+    fn to_json(&self) -> JsonValue {
+        jsonway::object(|json| {
+            json.set("status", self.status.as_str().to_string());
+            json.object("checks", |json| {
+                for (name, result) in self.checks.iter() {
+                    json.object(name.clone(), |json| {
+                        json.set("status", result.status.as_str().to_string());
+                        if let Some(ref details) = result.details {
+                            json.set("details", details.clone());
+                        }
+                    });
+                }
+            });
+        }).unwrap()
+    }
+}
+
+/// Named health checks.
+///
+/// Every check runs in its own thread and is reported as down when it panics
+/// or takes longer than its timeout. A check that timed out keeps running in
+/// the background and is not started again until it finishes, the probes
+/// that come meanwhile wait for that run.
+pub struct Health {
+    checks: Vec<Check>,
+    default_timeout: Duration,
+    cache_for: Option<Duration>,
+    liveness_cache: Mutex<Option<(Instant, Report)>>,
+    readiness_cache: Mutex<Option<(Instant, Report)>>
+}
+
+impl Health {
+    pub fn new() -> Health {
+        Health {
+            checks: vec![],
+            default_timeout: Duration::from_secs(5),
+            cache_for: None,
+            liveness_cache: Mutex::new(None),
+            readiness_cache: Mutex::new(None)
+        }
+    }
+
+    pub fn build<F>(builder: F) -> Health where F: FnOnce(&mut Health) {
+        let mut health = Health::new();
+        builder(&mut health);
+
+        health
+    }
+
+    pub fn liveness<F: 'static>(&mut self, name: &str, check: F) where F: Fn() -> CheckResult + Send+Sync {
+        self.add(name, Kind::Liveness, Arc::new(check));
+    }
+
+    pub fn readiness<F: 'static>(&mut self, name: &str, check: F) where F: Fn() -> CheckResult + Send+Sync {
+        self.add(name, Kind::Readiness, Arc::new(check));
+    }
+
+    fn add(&mut self, name: &str, kind: Kind, check: CheckFn) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            kind: kind,
+            timeout: None,
+            check: check,
+            running: Arc::new(Mutex::new(None))
+        });
+    }
+
+    /// Timeout of the check named `name`
+    pub fn timeout(&mut self, name: &str, timeout: Duration) {
+        for check in self.checks.iter_mut().filter(|check| check.name == name) {
+            check.timeout = Some(timeout);
+        }
+    }
+
+    /// Timeout of the checks without their own one, 5 seconds by default
+    pub fn default_timeout(&mut self, timeout: Duration) {
+        self.default_timeout = timeout;
+    }
+
+    /// Reuses the results of a run for `ttl`
+    pub fn cache_for(&mut self, ttl: Duration) {
+        self.cache_for = Some(ttl);
+    }
+
+    /// Runs the checks of `kind`, readiness also includes the liveness checks
+    pub fn report(&self, kind: Kind) -> Report {
+        let cache = match kind {
+            Kind::Liveness => &self.liveness_cache,
+            Kind::Readiness => &self.readiness_cache
+        };
+
+        if let (Some(ttl), &Some((ref at, ref report))) = (self.cache_for, &*lock(cache)) {
+            if at.elapsed() < ttl {
+                return report.clone();
+            }
+        }
+
+        let report = self.run(kind);
+        if self.cache_for.is_some() {
+            *lock(cache) = Some((Instant::now(), report.clone()));
+        }
+
+        report
+    }
+
+    fn run(&self, kind: Kind) -> Report {
+        let pending: Vec<(&Check, Arc<Run>)> = self.checks.iter()
+            .filter(|check| kind == Kind::Readiness || check.kind == Kind::Liveness)
+            .map(|check| (check, check.start()))
+            .collect();
+
+        let started = Instant::now();
+        let mut checks = BTreeMap::new();
+        for (check, run) in pending {
+            let timeout = check.timeout.unwrap_or(self.default_timeout);
+            let left = if started.elapsed() < timeout { timeout - started.elapsed() } else { Duration::from_secs(0) };
+
+            let result = run.wait(left).unwrap_or_else(|| {
+                CheckResult::down(format!("Timed out after {}ms", duration_ms(timeout)).to_json())
+            });
+            checks.insert(check.name.clone(), result);
+        }
+
+        let up = checks.values().all(|result| result.status == Status::Up);
+        Report {
+            status: if up { Status::Up } else { Status::Down },
+            checks: checks
+        }
+    }
+}
+
+fn duration_ms(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+/// API answering `200 OK` when the checks are up and `503 Service Unavailable`
+/// otherwise, at `path/live` for liveness and at `path/ready` for readiness
+pub fn create_api(path: &str, health: Health) -> framework::Api {
+    let health = Arc::new(health);
+
+    framework::Api::build(|api| {
+        api.namespace(path, |health_ns| {
+            for &(kind, name, summary) in [
+                (Kind::Liveness, "live", "Run liveness checks"),
+                (Kind::Readiness, "ready", "Run readiness checks")
+            ].iter() {
+                let health = health.clone();
+                health_ns.get(name, move |endpoint| {
+                    endpoint.summary(summary);
+                    endpoint.handle(move |mut client, _params| {
+                        let report = health.report(kind);
+                        if report.status == Status::Down {
+                            client.set_status(status::StatusCode::ServiceUnavailable);
+                        }
+                        client.json(&report.to_json())
+                    })
+                });
+            }
+        })
+    })
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use typemap;
+
+use framework::{self, Nesting};
+use server::{mime, status};
+
+/// Application extension key of the registry
+pub struct MetricsKey;
+
+impl typemap::Key for MetricsKey {
+    type Value = Arc<Metrics>;
+}
+
+/// Labels of a series. Route templates are used instead of paths to keep
+/// the number of series bounded.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Labels {
+    pub route: String,
+    pub method: String,
+    pub version: String,
+    /// `2xx`, `4xx`, ...
+    pub status: String
+}
+
+#[derive(Clone, Debug)]
+struct Series {
+    count: u64,
+    sum: f64,
+    /// Cumulative counts of the buckets
+    buckets: Vec<u64>
+}
+
+/// Request counters and latency histograms of the endpoints
+pub struct Metrics {
+    prefix: String,
+    buckets: Vec<f64>,
+    series: Mutex<BTreeMap<Labels, Series>>
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            prefix: "rustless".to_string(),
+            buckets: vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0],
+            series: Mutex::new(BTreeMap::new())
+        }
+    }
+
+    pub fn build<F>(builder: F) -> Metrics where F: FnOnce(&mut Metrics) {
+        let mut metrics = Metrics::new();
+        builder(&mut metrics);
+
+        metrics
+    }
+
+    /// Prefix of the metric names, `rustless` by default
+    pub fn prefix(&mut self, prefix: &str) {
+        self.prefix = prefix.to_string();
+    }
+
+    /// Upper bounds of the latency histogram buckets in seconds
+    pub fn buckets(&mut self, buckets: &[f64]) {
+        self.buckets = buckets.to_vec();
+    }
+
+    pub fn observe(&self, route: &framework::MatchedRoute, status: status::StatusCode, latency: Duration) {
+        let labels = Labels {
+            route: route.route.clone(),
+            method: route.method.to_string(),
+            version: route.version.clone().unwrap_or(String::new()),
+            status: format!("{}xx", status.to_u16() / 100)
+        };
+        let seconds = latency.as_secs() as f64 + latency.subsec_nanos() as f64 / 1e9;
+
+        let mut series = self.series.lock().unwrap();
+        let buckets_len = self.buckets.len();
+        let series = series.entry(labels).or_insert_with(|| Series {
+            count: 0,
+            sum: 0.0,
+            buckets: vec![0; buckets_len]
+        });
+
+        series.count += 1;
+        series.sum += seconds;
+        for (i, bound) in self.buckets.iter().enumerate() {
+            if seconds <= *bound {
+                series.buckets[i] += 1;
+            }
+        }
+    }
+
+    /// Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let series = self.series.lock().unwrap();
+        let requests = format!("{}_requests_total", self.prefix);
+        let duration = format!("{}_request_duration_seconds", self.prefix);
+
+        let mut text = String::new();
+        text.push_str(&format!("# HELP {} Number of handled requests.\n", requests));
+        text.push_str(&format!("# TYPE {} counter\n", requests));
+        for (labels, series) in series.iter() {
+            text.push_str(&format!("{}{{{}}} {}\n", requests, format_labels(labels, None), series.count));
+        }
+
+        text.push_str(&format!("# HELP {} Latency of handled requests.\n", duration));
+        text.push_str(&format!("# TYPE {} histogram\n", duration));
+        for (labels, series) in series.iter() {
+            for (bound, count) in self.buckets.iter().zip(series.buckets.iter()) {
+                let le = bound.to_string();
+                text.push_str(&format!("{}_bucket{{{}}} {}\n", duration, format_labels(labels, Some(&le)), count));
+            }
+            text.push_str(&format!("{}_bucket{{{}}} {}\n", duration, format_labels(labels, Some("+Inf")), series.count));
+            text.push_str(&format!("{}_sum{{{}}} {}\n", duration, format_labels(labels, None), series.sum));
+            text.push_str(&format!("{}_count{{{}}} {}\n", duration, format_labels(labels, None), series.count));
+        }
+
+        text
+    }
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs = vec![
+        ("route", &labels.route[..]),
+        ("method", &labels.method[..]),
+        ("version", &labels.version[..]),
+        ("status", &labels.status[..])
+    ];
+    if let Some(le) = le {
+        pairs.push(("le", le));
+    }
+
+    let pairs: Vec<String> = pairs.iter().map(|&(name, value)| format!("{}=\"{}\"", name, escape(value))).collect();
+    pairs.join(",")
+}
+
+fn escape(value: &str) -> String {
+    value.replace("\\", "\\\\").replace("\"", "\\\"").replace("\n", "\\n")
+}
+
+/// Collects metrics of every endpoint of the application, requests that
+/// match no endpoint are not counted
+pub fn enable(app: &mut framework::Application, metrics: Metrics) {
+    let metrics = Arc::new(metrics);
+    app.ext.insert::<MetricsKey>(metrics.clone());
+    app.on_response(move |req, response| {
+        let started = req.ext().get::<framework::RequestStart>();
+        if let (Some(route), Some(started)) = (req.ext().get::<framework::MatchedRoute>(), started) {
+            metrics.observe(route, response.status, started.elapsed());
+        }
+    });
+}
+
+/// API serving the metrics in the Prometheus text format at `path`
+pub fn create_api(path: &str) -> framework::Api {
+    framework::Api::build(|api| {
+        api.namespace(path, |metrics_ns| {
+            metrics_ns.get("", |endpoint| {
+                endpoint.summary("Get metrics in the Prometheus text format");
+                endpoint.handle(|mut client, _params| {
+                    client.set_content_type(mime::Mime(mime::TopLevel::Text, mime::SubLevel::Plain,
+                        vec![(mime::Attr::Ext("version".to_string()), mime::Value::Ext("0.0.4".to_string()))]));
+                    let text = client.app.ext.get::<MetricsKey>().map_or(String::new(), |metrics| metrics.render());
+                    client.text(text)
+                })
+            })
+        })
+    })
+}
+
+#[test]
+fn it_renders_histograms() {
+    let metrics = Metrics::build(|metrics| metrics.buckets(&[0.1, 1.0]));
+    let route = framework::MatchedRoute {
+        method: ::server::method::Method::Get,
+        route: "/api/users/:user_id".to_string(),
+        version: Some("v1".to_string())
+    };
+    metrics.observe(&route, status::StatusCode::Ok, Duration::from_millis(50));
+    metrics.observe(&route, status::StatusCode::Ok, Duration::from_millis(500));
+    metrics.observe(&route, status::StatusCode::NotFound, Duration::from_millis(5));
+
+    let text = metrics.render();
+    let labels = "route=\"/api/users/:user_id\",method=\"GET\",version=\"v1\",status=\"2xx\"";
+    assert!(text.contains(&format!("rustless_requests_total{{{}}} 2\n", labels)));
+    assert!(text.contains(&format!("rustless_request_duration_seconds_bucket{{{},le=\"0.1\"}} 1\n", labels)));
+    assert!(text.contains(&format!("rustless_request_duration_seconds_bucket{{{},le=\"1\"}} 2\n", labels)));
+    assert!(text.contains(&format!("rustless_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2\n", labels)));
+    assert!(text.contains(&format!("rustless_request_duration_seconds_count{{{}}} 2\n", labels)));
+    assert!(text.contains("status=\"4xx\"} 1\n"));
+}
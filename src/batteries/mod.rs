@@ -1,4 +1,14 @@
-
 pub mod cookie;
 pub mod swagger;
-pub mod schemes;
\ No newline at end of file
+pub mod schemes;
+pub mod problem;
+pub mod auth;
+pub mod cors;
+pub mod rate_limit;
+pub mod request_id;
+pub mod access_log;
+pub mod metrics;
+pub mod health;
+pub mod compression;
+pub mod conditional;
+pub mod cache;
@@ -0,0 +1,154 @@
+use backend;
+use errors;
+use framework;
+use json::{self, JsonValue, ToJson};
+use server::status::{self, StatusCode};
+use server::{mime, header};
+
+/// Problem Details for HTTP APIs as described in RFC 7807.
+pub struct ProblemDetails {
+    /// A URI reference that identifies the problem type. Defaults to "about:blank".
+    pub type_: String,
+    /// A short, human-readable summary of the problem type.
+    pub title: String,
+    /// The HTTP status code generated by the origin server for this occurrence of the problem.
+    pub status: StatusCode,
+    /// A human-readable explanation specific to this occurrence of the problem.
+    pub detail: Option<String>,
+    /// A URI reference that identifies the specific occurrence of the problem.
+    pub instance: Option<String>,
+    /// Extension members that are serialized next to the standard ones.
+    pub extensions: json::Object
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode) -> ProblemDetails {
+        ProblemDetails {
+            type_: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Unknown Error").to_string(),
+            status: status,
+            detail: None,
+            instance: None,
+            extensions: json::Object::new()
+        }
+    }
+
+    pub fn set_extension(&mut self, name: &str, value: JsonValue) {
+        self.extensions.insert(name.to_string(), value);
+    }
+
+    pub fn to_response(&self) -> backend::Response {
+        let mut response = backend::Response::new(self.status);
+        response.set_header(header::ContentType(content_type()));
+        response.replace_body(Box::new(self.to_json().to_string()));
+        response
+    }
+}
+
+impl ToJson for ProblemDetails {
+    fn to_json(&self) -> JsonValue {
+        let mut problem = self.extensions.clone();
+        problem.insert("type".to_string(), self.type_.to_json());
+        problem.insert("title".to_string(), self.title.to_json());
+        problem.insert("status".to_string(), self.status.to_u16().to_json());
+        if self.detail.is_some() {
+            problem.insert("detail".to_string(), self.detail.clone().unwrap().to_json());
+        }
+        if self.instance.is_some() {
+            problem.insert("instance".to_string(), self.instance.clone().unwrap().to_json());
+        }
+        JsonValue::Object(problem)
+    }
+}
+
+/// The `application/problem+json` media type
+pub fn content_type() -> mime::Mime {
+    mime::Mime(mime::TopLevel::Application, mime::SubLevel::Ext("problem+json".to_string()), vec![])
+}
+
+pub type ProblemExtender = Box<Fn(&errors::Error, &mut ProblemDetails) + 'static + Send + Sync>;
+
+/// Converts errors into problem documents.
+///
+/// Built-in errors are described out of the box, your own error types can
+/// contribute members with `extend`.
+pub struct Problems {
+    extenders: Vec<ProblemExtender>
+}
+
+impl Problems {
+    pub fn new() -> Problems {
+        Problems {
+            extenders: vec![]
+        }
+    }
+
+    pub fn build<F>(builder: F) -> Problems where F: FnOnce(&mut Problems) {
+        let mut problems = Problems::new();
+        builder(&mut problems);
+
+        problems
+    }
+
+    /// Registers a callback that fills the problem document for errors of type `E`.
+    pub fn extend<E: errors::Error, F: 'static>(&mut self, extender: F)
+    where F: Fn(&E, &mut ProblemDetails) + Send+Sync {
+        self.extenders.push(Box::new(move |err, problem| {
+            match err.downcast::<E>() {
+                Some(err) => extender(err, problem),
+                None => ()
+            }
+        }));
+    }
+
+    pub fn to_problem(&self, err: &errors::Error, context: &framework::ErrorContext) -> ProblemDetails {
+        // Details of errors without a public message are not exposed to clients
+        let mut problem = match context.app.find_http_error(err) {
+            Some(http_error) => {
+                let mut problem = ProblemDetails::new(http_error.status());
+                problem.detail = http_error.public_message();
+                problem
+            },
+            None => ProblemDetails::new(status::StatusCode::InternalServerError)
+        };
+
+        if let Some(validation) = err.downcast::<errors::Validation>() {
+            problem.set_extension("errors", validation.to_json());
+        }
+
+        problem.instance = Some(format!("/{}", context.request.url().path().join("/")));
+
+        for extender in self.extenders.iter() {
+            extender(err, &mut problem);
+        }
+
+        problem
+    }
+}
+
+/// Installs an error formatter that answers every error with a problem document.
+pub fn enable(api: &mut framework::Api, problems: Problems) {
+    api.error_formatter_with_context(move |err, context| {
+        let mut response = problems.to_problem(err, context).to_response();
+        if let Some(http_error) = context.app.find_http_error(err) {
+            response.headers.extend(http_error.headers().iter());
+        }
+
+        Some(response)
+    });
+}
+
+#[test]
+fn it_serializes_problem_details() {
+    let mut problem = ProblemDetails::new(status::StatusCode::BadRequest);
+    problem.detail = Some("Balance is too low".to_string());
+    problem.set_extension("balance", 30.to_json());
+
+    let json = problem.to_json();
+    assert_eq!(json.find("type").unwrap().as_str().unwrap(), "about:blank");
+    assert_eq!(json.find("title").unwrap().as_str().unwrap(), "Bad Request");
+    assert_eq!(json.find("status").unwrap().as_u64().unwrap(), 400);
+    assert_eq!(json.find("detail").unwrap().as_str().unwrap(), "Balance is too low");
+    assert_eq!(json.find("balance").unwrap().as_u64().unwrap(), 30);
+    assert!(json.find("instance").is_none());
+}
@@ -0,0 +1,349 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use typemap;
+
+use backend;
+use errors;
+use framework;
+use framework::{Nesting};
+use json::{JsonValue};
+use batteries::auth::api_key::KeyLocation;
+
+/// How requests are counted
+#[derive(Clone, Copy, Debug)]
+pub enum Algorithm {
+    /// Bursts of up to `capacity` requests, refilled at a steady rate
+    TokenBucket { capacity: u64, refill_per_second: f64 },
+    /// At most `limit` requests in any window of `window_seconds`
+    SlidingWindow { limit: u64, window_seconds: u64 }
+}
+
+/// The counting state of a single client
+#[derive(Clone, Debug)]
+pub enum Bucket {
+    Tokens { tokens: f64, updated: f64 },
+    Window(VecDeque<f64>)
+}
+
+/// Outcome of counting a request
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u64,
+    pub remaining: u64,
+    /// Seconds until the limit is fully restored
+    pub reset: u64,
+    /// Seconds until the next request is allowed, for rejected requests
+    pub retry_after: u64
+}
+
+impl Algorithm {
+    pub fn limit(&self) -> u64 {
+        match self {
+            &Algorithm::TokenBucket { capacity, .. } => capacity,
+            &Algorithm::SlidingWindow { limit, .. } => limit
+        }
+    }
+
+    /// Counts a request made at `now` (seconds since the epoch)
+    pub fn hit(&self, bucket: Option<Bucket>, now: f64) -> (Bucket, Decision) {
+        match *self {
+            Algorithm::TokenBucket { capacity, refill_per_second } => {
+                let capacity = capacity as f64;
+                let tokens = match bucket {
+                    Some(Bucket::Tokens { tokens, updated }) => {
+                        (tokens + (now - updated).max(0.0) * refill_per_second).min(capacity)
+                    },
+                    _ => capacity
+                };
+
+                let allowed = tokens >= 1.0;
+                let tokens = if allowed { tokens - 1.0 } else { tokens };
+                let decision = Decision {
+                    allowed: allowed,
+                    limit: capacity as u64,
+                    remaining: tokens.floor() as u64,
+                    reset: ((capacity - tokens) / refill_per_second).ceil() as u64,
+                    retry_after: if allowed { 0 } else { ((1.0 - tokens) / refill_per_second).ceil() as u64 }
+                };
+
+                (Bucket::Tokens { tokens: tokens, updated: now }, decision)
+            },
+            Algorithm::SlidingWindow { limit, window_seconds } => {
+                let window = window_seconds as f64;
+                let mut hits = match bucket {
+                    Some(Bucket::Window(hits)) => hits,
+                    _ => VecDeque::new()
+                };
+                while hits.front().map_or(false, |hit| *hit <= now - window) {
+                    hits.pop_front();
+                }
+
+                let allowed = (hits.len() as u64) < limit;
+                if allowed {
+                    hits.push_back(now);
+                }
+
+                let until_expired = |hit: Option<&f64>| hit.map_or(0, |hit| (hit + window - now).ceil() as u64);
+                let decision = Decision {
+                    allowed: allowed,
+                    limit: limit,
+                    remaining: limit.saturating_sub(hits.len() as u64),
+                    reset: until_expired(hits.back()),
+                    retry_after: if allowed { 0 } else { until_expired(hits.front()) }
+                };
+
+                (Bucket::Window(hits), decision)
+            }
+        }
+    }
+
+    /// Whether the bucket is back to its full limit at `now`, so it can be forgotten
+    pub fn is_idle(&self, bucket: &Bucket, now: f64) -> bool {
+        match (*self, bucket) {
+            (Algorithm::TokenBucket { capacity, refill_per_second }, &Bucket::Tokens { tokens, updated }) => {
+                tokens + (now - updated).max(0.0) * refill_per_second >= capacity as f64
+            },
+            (Algorithm::SlidingWindow { window_seconds, .. }, &Bucket::Window(ref hits)) => {
+                hits.back().map_or(true, |hit| *hit <= now - window_seconds as f64)
+            },
+            _ => true
+        }
+    }
+}
+
+/// Keeps the buckets of the clients
+pub trait Store: Send + Sync {
+    fn hit(&self, key: &str, algorithm: &Algorithm, now: f64) -> Decision;
+}
+
+struct Buckets {
+    /// Each bucket with the algorithm it is counted by
+    buckets: HashMap<String, (Algorithm, Bucket)>,
+    swept: f64
+}
+
+/// Keeps the buckets in process memory.
+///
+/// Buckets that are back to their full limit are dropped by a sweep that runs
+/// on a hit at most once every `sweep_every` seconds (60 by default).
+pub struct MemoryStore {
+    buckets: Mutex<Buckets>,
+    sweep_every: f64
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore {
+            buckets: Mutex::new(Buckets { buckets: HashMap::new(), swept: 0.0 }),
+            sweep_every: 60.0
+        }
+    }
+
+    pub fn sweep_every(&mut self, seconds: u64) {
+        self.sweep_every = seconds as f64;
+    }
+
+    /// Number of clients currently tracked
+    pub fn len(&self) -> usize {
+        self.buckets.lock().unwrap().buckets.len()
+    }
+}
+
+impl Store for MemoryStore {
+    fn hit(&self, key: &str, algorithm: &Algorithm, now: f64) -> Decision {
+        let mut state = self.buckets.lock().unwrap();
+        if now - state.swept >= self.sweep_every {
+            state.buckets.retain(|_, &mut (ref algorithm, ref bucket)| !algorithm.is_idle(bucket, now));
+            state.swept = now;
+        }
+
+        let (bucket, decision) = algorithm.hit(state.buckets.remove(key).map(|(_, bucket)| bucket), now);
+        state.buckets.insert(key.to_string(), (*algorithm, bucket));
+        decision
+    }
+}
+
+/// Identifies the client a request is counted for
+pub enum KeyExtractor {
+    RemoteAddr,
+    ApiKey(KeyLocation),
+    Custom(Box<for<'a> Fn(&framework::Client<'a>) -> Option<String> + Send + Sync>)
+}
+
+impl KeyExtractor {
+    pub fn extract(&self, client: &framework::Client) -> Option<String> {
+        match self {
+            &KeyExtractor::RemoteAddr => Some(client.request.remote_addr().ip().to_string()),
+            &KeyExtractor::ApiKey(ref location) => location.extract(client.request),
+            &KeyExtractor::Custom(ref extractor) => extractor(client)
+        }
+    }
+}
+
+/// The decision of the innermost limiter that counted the request
+pub struct DecisionKey;
+
+impl typemap::Key for DecisionKey {
+    type Value = Decision;
+}
+
+/// Middleware that rejects requests over the limit with `errors::TooManyRequests`.
+///
+/// Mount it with `around_middleware` on an `Api`, a `Namespace` or an `Endpoint`
+/// and call `enable` on the `Api` to send the `RateLimit-*` headers.
+/// Requests without a key are not limited.
+///
+/// The store keys are prefixed with the name of the limiter, so limiters
+/// sharing a store count requests apart.
+pub struct RateLimiter {
+    name: String,
+    algorithm: Algorithm,
+    key: KeyExtractor,
+    store: Arc<Store>
+}
+
+static LIMITERS: AtomicUsize = AtomicUsize::new(0);
+
+impl RateLimiter {
+    pub fn new(algorithm: Algorithm, key: KeyExtractor) -> RateLimiter {
+        RateLimiter {
+            name: format!("limiter-{}", LIMITERS.fetch_add(1, Ordering::SeqCst)),
+            algorithm: algorithm,
+            key: key,
+            store: Arc::new(MemoryStore::new())
+        }
+    }
+
+    /// Names the limiter instead of numbering it in the order limiters are
+    /// created, for stores shared by several processes
+    pub fn name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    pub fn store(&mut self, store: Arc<Store>) {
+        self.store = store;
+    }
+
+    pub fn check(&self, client: &framework::Client) -> Option<Decision> {
+        self.key.extract(client).map(|key| {
+            self.store.hit(&format!("{}:{}", self.name, key), &self.algorithm, now())
+        })
+    }
+}
+
+/// Adds the `RateLimit-*` headers of the counted requests to the final
+/// responses of the node, error responses included.
+pub fn enable<N: Nesting>(node: &mut N) {
+    node.on_response(|req, response| {
+        if let Some(decision) = req.ext().get::<DecisionKey>() {
+            set_headers(response, decision);
+        }
+    });
+}
+
+fn set_headers(response: &mut backend::Response, decision: &Decision) {
+    response.headers.set_raw("RateLimit-Limit", vec![decision.limit.to_string().into_bytes()]);
+    response.headers.set_raw("RateLimit-Remaining", vec![decision.remaining.to_string().into_bytes()]);
+    response.headers.set_raw("RateLimit-Reset", vec![decision.reset.to_string().into_bytes()]);
+}
+
+impl framework::Around for RateLimiter {
+    fn call<'a, 'n>(&self, client: framework::Client<'a>, params: &mut JsonValue, next: framework::Next<'n>) -> framework::client::ClientResult<'a> {
+        let decision = match self.check(&client) {
+            Some(decision) => decision,
+            None => return next.run(client, params)
+        };
+
+        let allowed = decision.allowed;
+        let error = errors::TooManyRequests {
+            limit: decision.limit,
+            reset: decision.reset,
+            retry_after: decision.retry_after
+        };
+        client.request.ext_mut().insert::<DecisionKey>(decision);
+
+        if !allowed {
+            return Err(error_response!(error));
+        }
+
+        next.run(client, params)
+    }
+}
+
+fn now() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9)
+        .unwrap_or(0.0)
+}
+
+#[test]
+fn it_refills_token_buckets() {
+    let algorithm = Algorithm::TokenBucket { capacity: 2, refill_per_second: 0.5 };
+
+    let (bucket, decision) = algorithm.hit(None, 100.0);
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 1);
+
+    let (bucket, _) = algorithm.hit(Some(bucket), 100.0);
+    let (bucket, decision) = algorithm.hit(Some(bucket), 100.0);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, 2);
+    assert_eq!(decision.reset, 4);
+
+    let (_, decision) = algorithm.hit(Some(bucket), 102.0);
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 0);
+}
+
+#[test]
+fn it_slides_windows() {
+    let algorithm = Algorithm::SlidingWindow { limit: 2, window_seconds: 10 };
+
+    let (bucket, _) = algorithm.hit(None, 100.0);
+    let (bucket, decision) = algorithm.hit(Some(bucket), 105.0);
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 0);
+
+    let (bucket, decision) = algorithm.hit(Some(bucket), 108.0);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, 2);
+
+    let (_, decision) = algorithm.hit(Some(bucket), 110.0);
+    assert!(decision.allowed);
+    assert_eq!(decision.reset, 10);
+}
+
+#[test]
+fn it_sweeps_idle_buckets() {
+    let algorithm = Algorithm::TokenBucket { capacity: 2, refill_per_second: 0.1 };
+    let mut store = MemoryStore::new();
+    store.sweep_every(10);
+
+    store.hit("a", &algorithm, 100.0);
+    store.hit("b", &algorithm, 105.0);
+    assert_eq!(store.len(), 2);
+
+    // "a" has refilled and "b" has not, so only "a" is swept
+    store.hit("c", &algorithm, 110.5);
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.hit("b", &algorithm, 110.5).remaining, 0);
+}
+
+#[test]
+fn it_sweeps_buckets_by_their_own_algorithm() {
+    let slow = Algorithm::TokenBucket { capacity: 2, refill_per_second: 0.01 };
+    let fast = Algorithm::TokenBucket { capacity: 2, refill_per_second: 1.0 };
+    let mut store = MemoryStore::new();
+    store.sweep_every(10);
+
+    store.hit("slow", &slow, 100.0);
+    store.hit("fast", &fast, 100.0);
+
+    // "slow" has not refilled yet, although it would have at the rate of `fast`
+    store.hit("fast", &fast, 110.0);
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.hit("slow", &slow, 110.0).remaining, 0);
+}
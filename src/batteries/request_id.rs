@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use ring::rand::{SecureRandom, SystemRandom};
+use typemap;
+
+use backend;
+use framework;
+
+/// Request extension key of the request id
+pub struct RequestIdKey;
+
+impl typemap::Key for RequestIdKey {
+    type Value = String;
+}
+
+pub type Generator = Box<Fn() -> String + 'static + Send + Sync>;
+
+/// Request id settings.
+///
+/// The id of the incoming header is kept when it is at most 200 visible ASCII
+/// characters, otherwise a random UUID is generated.
+pub struct RequestId {
+    header: String,
+    trust_incoming: bool,
+    generator: Generator
+}
+
+impl RequestId {
+    pub fn new() -> RequestId {
+        RequestId {
+            header: "X-Request-Id".to_string(),
+            trust_incoming: true,
+            generator: Box::new(generate)
+        }
+    }
+
+    pub fn build<F>(builder: F) -> RequestId where F: FnOnce(&mut RequestId) {
+        let mut request_id = RequestId::new();
+        builder(&mut request_id);
+
+        request_id
+    }
+
+    /// Header the id is read from and echoed in
+    pub fn header(&mut self, name: &str) {
+        self.header = name.to_string();
+    }
+
+    /// Always generate ids, e.g. for APIs exposed to untrusted clients
+    pub fn trust_incoming(&mut self, trust: bool) {
+        self.trust_incoming = trust;
+    }
+
+    pub fn generator<F: 'static>(&mut self, generator: F) where F: Fn() -> String + Send+Sync {
+        self.generator = Box::new(generator);
+    }
+
+    fn incoming(&self, req: &backend::Request) -> Option<String> {
+        if !self.trust_incoming {
+            return None;
+        }
+
+        req.headers().get_raw(&self.header)
+            .and_then(|values| values.first())
+            .and_then(|value| String::from_utf8(value.clone()).ok())
+            .map(|value| value.trim().to_string())
+            .and_then(|value| if is_valid(&value) { Some(value) } else { None })
+    }
+
+    /// Stores the id of the request in its extensions and prefixes the log lines with it
+    pub fn assign(&self, req: &mut backend::Request) {
+        let id = self.incoming(req).unwrap_or_else(|| (self.generator)());
+        req.ext_mut().insert::<framework::LogPrefix>(format!("[{}] ", id));
+        req.ext_mut().insert::<RequestIdKey>(id);
+    }
+
+    pub fn echo(&self, req: &backend::Request, response: &mut backend::Response) {
+        if let Some(id) = req.request_id() {
+            response.headers.set_raw(self.header.clone(), vec![id.as_bytes().to_vec()]);
+        }
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 200 && id.bytes().all(|b| b > 0x20 && b < 0x7f)
+}
+
+/// Random version 4 UUID
+pub fn generate() -> String {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new().fill(&mut bytes).expect("System random generator failed");
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}",
+        hex[0..4].concat(), hex[4..6].concat(), hex[6..8].concat(), hex[8..10].concat(), hex[10..16].concat())
+}
+
+pub trait RequestIdExt {
+    fn request_id(&self) -> Option<&str>;
+}
+
+impl<'r> RequestIdExt for (backend::Request + 'r) {
+    fn request_id(&self) -> Option<&str> {
+        self.ext().get::<RequestIdKey>().map(|id| &id[..])
+    }
+}
+
+impl<'a> RequestIdExt for framework::Client<'a> {
+    fn request_id(&self) -> Option<&str> {
+        self.request.ext().get::<RequestIdKey>().map(|id| &id[..])
+    }
+}
+
+/// Assigns an id to every request handled by the application and echoes it
+/// in the responses
+pub fn enable(app: &mut framework::Application, request_id: RequestId) {
+    let request_id = Arc::new(request_id);
+    let assigner = request_id.clone();
+    app.on_request(move |req| assigner.assign(req));
+    app.on_response(move |req, response| request_id.echo(req, response));
+}
+
+#[test]
+fn it_generates_uuids() {
+    let id = generate();
+    assert_eq!(id.len(), 36);
+    assert_eq!(&id[14..15], "4");
+    assert!(id != generate());
+}
+
+#[test]
+fn it_rejects_unsafe_ids() {
+    assert!(is_valid("7f0c-aa"));
+    assert!(!is_valid(""));
+    assert!(!is_valid("abc def"));
+    assert!(!is_valid("abc\ninjected"));
+    assert!(!is_valid(&"a".repeat(201)));
+}
@@ -4,7 +4,7 @@ use std::collections;
 use jsonway::{self};
 
 use json::{self, JsonValue, ToJson};
-use framework::{self, Nesting};
+use framework::{self, Nesting, Node};
 use server::mime;
 use server::header;
 use server::method;
@@ -347,10 +347,20 @@ pub fn build_spec(app: &framework::Application, spec: Spec) -> JsonValue {
         json.object("paths", |paths| {
             fill_paths(WalkContext {
                 path: "",
-                params: vec![]
+                params: vec![],
+                security: app.root_api.get_security().schemes.clone(),
+                scopes: app.root_api.get_security().scopes.clone(),
+                roles: role_levels(vec![], app.root_api.get_security())
             }, paths, &app.root_api.handlers);
         });
 
+        let mut definitions = collections::BTreeMap::new();
+        collect_security_definitions(&app.root_api, &mut definitions);
+        if !definitions.is_empty() {
+            // Security scheme definitions that can be used across the specification.
+            json.set("securityDefinitions", JsonValue::Object(definitions));
+        }
+
         // TODO Implement the rest of the spec
 
         // // An object to hold data types produced and consumed by operations.
@@ -370,11 +380,6 @@ pub fn build_spec(app: &framework::Application, spec: Spec) -> JsonValue {
 
         // });
 
-        // // Security scheme definitions that can be used across the specification.
-        // json.object("securityDefinitions", |security_definitions| {
-
-        // });
-
         // // A declaration of which security schemes are applied for the API as a whole.
         // // The list of values describes alternative security schemes that can be used
         // // (that is, there is a logical OR between the security requirements).
@@ -424,7 +429,39 @@ pub fn create_api(path: &str) -> framework::Api {
 #[allow(dead_code)]
 struct WalkContext<'a> {
     pub path: &'a str,
-    pub params: Vec<Param>
+    pub params: Vec<Param>,
+    pub security: Vec<framework::SecurityScheme>,
+    pub scopes: Vec<String>,
+    /// The roles of every level, one of each is required
+    pub roles: Vec<Vec<String>>
+}
+
+fn role_levels(mut levels: Vec<Vec<String>>, security: &framework::Security) -> Vec<Vec<String>> {
+    if !security.roles.is_empty() {
+        levels.push(security.roles.clone());
+    }
+    levels
+}
+
+/// Only OAuth2 requirements list scopes, the others must be empty
+fn is_oauth2(scheme: &framework::SecurityScheme) -> bool {
+    scheme.definition.find("type").and_then(|type_| type_.as_str()) == Some("oauth2")
+}
+
+/// Collects the security schemes used anywhere in the tree
+fn collect_security_definitions(node: &Node, definitions: &mut json::Object) {
+    for scheme in node.get_security().schemes.iter() {
+        definitions.insert(scheme.name.clone(), scheme.definition.clone());
+    }
+
+    for handler_ in node.get_handlers().iter() {
+        let handler = &**handler_ as &framework::ApiHandler;
+        if handler.is::<framework::Api>() {
+            collect_security_definitions(handler.downcast::<framework::Api>().unwrap(), definitions);
+        } else if handler.is::<framework::Namespace>() {
+            collect_security_definitions(handler.downcast::<framework::Namespace>().unwrap(), definitions);
+        }
+    }
 }
 
 /// Walks through the tree and collects the info about Endpoints
@@ -449,9 +486,18 @@ fn fill_paths<'a>(mut context: WalkContext<'a>, paths: &mut jsonway::ObjectBuild
                 }
             }
 
+            let mut security = context.security.clone();
+            security.extend(api.get_security().schemes.iter().cloned());
+            let mut scopes = context.scopes.clone();
+            scopes.extend(api.get_security().scopes.iter().cloned());
+            let roles = role_levels(context.roles.clone(), api.get_security());
+
             fill_paths(WalkContext{
                 path: &path,
-                params: context.params.clone()
+                params: context.params.clone(),
+                security: security,
+                scopes: scopes,
+                roles: roles
             }, paths, &api.handlers);
 
         } else if handler.is::<framework::Namespace>() {
@@ -463,9 +509,18 @@ fn fill_paths<'a>(mut context: WalkContext<'a>, paths: &mut jsonway::ObjectBuild
             let mut params = context.params.clone();
             params.extend(extract_params(&namespace.coercer, &namespace.path));
 
+            let mut security = context.security.clone();
+            security.extend(namespace.get_security().schemes.iter().cloned());
+            let mut scopes = context.scopes.clone();
+            scopes.extend(namespace.get_security().scopes.iter().cloned());
+            let roles = role_levels(context.roles.clone(), namespace.get_security());
+
             fill_paths(WalkContext{
                 path: &path,
                 params: params,
+                security: security,
+                scopes: scopes,
+                roles: roles
             }, paths, &namespace.handlers);
 
         } else if handler.is::<framework::Endpoint>() {
@@ -628,12 +683,32 @@ fn build_endpoint_definition(endpoint: &framework::Endpoint, context: &mut WalkC
         // // Default value is false.
         // def.set("deprecated", false);
 
-        // // A declaration of which security schemes are applied for this operation.
-        // // The list of values describes alternative security schemes that can be used
-        // // (that is, there is a logical OR between the security requirements).
-        // // This definition overrides any declared top-level security.
-        // // To remove a top-level security declaration, an empty array can be used.
-        // def.array("security", |security| {});
+        let mut schemes = context.security.clone();
+        schemes.extend(endpoint.security.schemes.iter().cloned());
+        let mut scopes = context.scopes.clone();
+        scopes.extend(endpoint.security.scopes.iter().cloned());
+        let roles = role_levels(context.roles.clone(), &endpoint.security);
+
+        if !schemes.is_empty() {
+            // A declaration of which security schemes are applied for this operation.
+            // The list of values describes alternative security requirements
+            // (that is, there is a logical OR between them), while all the schemes
+            // of one requirement must be satisfied. Every scheme inherited by the
+            // endpoint applies to it, so they all go into a single requirement.
+            def.array("security", |security| {
+                security.object(|requirement| {
+                    for scheme in schemes.iter() {
+                        let scopes = if is_oauth2(scheme) { scopes.clone() } else { vec![] };
+                        requirement.set(scheme.name.clone(), scopes);
+                    }
+                });
+            });
+        }
+
+        if !roles.is_empty() {
+            // Swagger has no notion of roles, one role of each list is required
+            def.set("x-roles", roles);
+        }
     }).unwrap()
 }
 
@@ -2,6 +2,10 @@ use std::io;
 pub use error::{Error};
 use std::error::Error as StdError;
 use valico;
+use jsonway;
+
+use json::{JsonValue};
+use server::{status, header};
 
 use super::backend;
 
@@ -30,6 +34,56 @@ macro_rules! error_response_boxed{
 }
 
 
+/// An error that knows how it should be reported to HTTP clients.
+///
+/// Implementing the trait is not enough: errors are passed around as
+/// `Box<Error>`, so the application only consults the implementations of
+/// the built-in errors and of the types registered with
+/// `Application::http_error::<E>()`. Unregistered errors are answered with
+/// `500 Internal Server Error` when no error formatter produced a response.
+pub trait HttpError: Error {
+    fn status(&self) -> status::StatusCode;
+
+    fn headers(&self) -> header::Headers {
+        header::Headers::new()
+    }
+
+    /// A message that is safe to show to clients
+    fn public_message(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Recovers the `HttpError` implementation of an error object of a known type.
+pub type HttpErrorCast = for<'e> fn(&'e Error) -> Option<&'e HttpError>;
+
+pub fn http_error_cast<E: HttpError>(err: &Error) -> Option<&HttpError> {
+    err.downcast::<E>().map(|err| err as &HttpError)
+}
+
+/// Casts for the errors defined in this module
+pub fn builtin_http_errors() -> Vec<HttpErrorCast> {
+    vec![
+        http_error_cast::<NotMatch>,
+        http_error_cast::<NotFound>,
+        http_error_cast::<QueryString>,
+        http_error_cast::<Validation>,
+        http_error_cast::<Body>,
+        http_error_cast::<File>,
+        http_error_cast::<NotAcceptable>,
+        http_error_cast::<Panic>,
+        http_error_cast::<MissingDependency>,
+        http_error_cast::<MissingHelper>,
+        http_error_cast::<Unauthorized>,
+        http_error_cast::<Forbidden>,
+        http_error_cast::<TooManyRequests>,
+        http_error_cast::<PayloadTooLarge>,
+        http_error_cast::<UnsupportedEncoding>,
+        http_error_cast::<PreconditionRequired>,
+        http_error_cast::<PreconditionFailed>
+    ]
+}
+
 macro_rules! impl_basic_err {
     ($err:ty, $code:expr) => {
         impl ::std::error::Error for $err {
@@ -50,20 +104,53 @@ macro_rules! impl_basic_err {
 pub struct NotMatch;
 impl_basic_err!(NotMatch, "NotMatch");
 
+impl HttpError for NotMatch {
+    fn status(&self) -> status::StatusCode { status::StatusCode::NotFound }
+}
+
 #[derive(Debug)]
 pub struct NotFound;
 impl_basic_err!(NotFound, "NotFound");
 
+impl HttpError for NotFound {
+    fn status(&self) -> status::StatusCode { status::StatusCode::NotFound }
+}
+
 #[derive(Debug)]
 pub struct QueryString;
 impl_basic_err!(QueryString, "QueryString");
 
+impl HttpError for QueryString {
+    fn status(&self) -> status::StatusCode { status::StatusCode::BadRequest }
+    fn public_message(&self) -> Option<String> { Some("Query string can't be parsed".to_string()) }
+}
+
 #[derive(Debug)]
 pub struct Validation {
     pub reason: valico::ValicoErrors
 }
 impl_basic_err!(Validation, "Validation");
 
+impl HttpError for Validation {
+    fn status(&self) -> status::StatusCode { status::StatusCode::BadRequest }
+    fn public_message(&self) -> Option<String> { Some("Request parameters are invalid".to_string()) }
+}
+
+impl Validation {
+    /// Lists every failed check with its JSON pointer `path`, `code` and `message`.
+    pub fn to_json(&self) -> JsonValue {
+        jsonway::array(|errors| {
+            for error in self.reason.iter() {
+                errors.object(|json| {
+                    json.set("path", error.get_path().to_string());
+                    json.set("code", error.get_code().to_string());
+                    json.set("message", error.get_detail().unwrap_or(error.get_title()).to_string());
+                });
+            }
+        }).unwrap()
+    }
+}
+
 #[derive(Debug)]
 pub struct Body {
     pub reason: String
@@ -78,10 +165,203 @@ impl Body {
 }
 impl_basic_err!(Body, "Body");
 
+impl HttpError for Body {
+    fn status(&self) -> status::StatusCode { status::StatusCode::BadRequest }
+    fn public_message(&self) -> Option<String> { Some(self.reason.clone()) }
+}
+
 #[derive(Debug)]
 pub struct File(pub io::Error);
 impl_basic_err!(File, "File");
 
+impl HttpError for File {
+    fn status(&self) -> status::StatusCode {
+        match self.0.kind() {
+            io::ErrorKind::NotFound => status::StatusCode::NotFound,
+            _ => status::StatusCode::InternalServerError
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NotAcceptable;
 impl_basic_err!(NotAcceptable, "NotAcceptable");
+
+impl HttpError for NotAcceptable {
+    fn status(&self) -> status::StatusCode { status::StatusCode::NotAcceptable }
+    fn public_message(&self) -> Option<String> { Some("Requested media type is not acceptable".to_string()) }
+}
+
+
+/// A panic caught while an endpoint was executed
+#[derive(Debug)]
+pub struct Panic {
+    pub message: String,
+    pub route: String
+}
+impl_basic_err!(Panic, "Panic");
+
+impl HttpError for Panic {
+    fn status(&self) -> status::StatusCode { status::StatusCode::InternalServerError }
+}
+
+/// Application state or a request-scoped value was requested but never registered
+#[derive(Debug)]
+pub struct MissingDependency {
+    pub type_name: String
+}
+
+impl ::std::error::Error for MissingDependency {
+    fn description(&self) -> &str {
+        "MissingDependency"
+    }
+}
+
+impl ::std::fmt::Display for MissingDependency {
+    fn fmt(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(formatter, "No state or provider is registered for `{}`", self.type_name)
+    }
+}
+
+impl HttpError for MissingDependency {
+    fn status(&self) -> status::StatusCode { status::StatusCode::InternalServerError }
+}
+
+/// A helper was called but none of the enclosing nodes defines it
+#[derive(Debug)]
+pub struct MissingHelper {
+    pub name: String
+}
+
+impl ::std::error::Error for MissingHelper {
+    fn description(&self) -> &str {
+        "MissingHelper"
+    }
+}
+
+impl ::std::fmt::Display for MissingHelper {
+    fn fmt(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(formatter, "No helper `{}` is defined for this endpoint", self.name)
+    }
+}
+
+impl HttpError for MissingHelper {
+    fn status(&self) -> status::StatusCode { status::StatusCode::InternalServerError }
+}
+
+/// The request lacks valid credentials for the authentication scheme of the endpoint
+#[derive(Debug)]
+pub struct Unauthorized {
+    /// The `WWW-Authenticate` challenge, e.g. `Basic realm="api"`
+    pub challenge: String,
+    pub reason: String
+}
+impl_basic_err!(Unauthorized, "Unauthorized");
+
+impl Unauthorized {
+    pub fn new(challenge: &str, reason: &str) -> Unauthorized {
+        Unauthorized {
+            challenge: challenge.to_string(),
+            reason: reason.to_string()
+        }
+    }
+}
+
+impl HttpError for Unauthorized {
+    fn status(&self) -> status::StatusCode { status::StatusCode::Unauthorized }
+    fn headers(&self) -> header::Headers {
+        let mut headers = header::Headers::new();
+        headers.set_raw("WWW-Authenticate", vec![self.challenge.clone().into_bytes()]);
+        headers
+    }
+    fn public_message(&self) -> Option<String> { Some(self.reason.clone()) }
+}
+
+/// The authenticated principal lacks the scopes or roles the endpoint requires
+#[derive(Debug)]
+pub struct Forbidden {
+    pub reason: String
+}
+impl_basic_err!(Forbidden, "Forbidden");
+
+impl HttpError for Forbidden {
+    fn status(&self) -> status::StatusCode { status::StatusCode::Forbidden }
+    fn public_message(&self) -> Option<String> { Some(self.reason.clone()) }
+}
+
+/// The client exceeded its rate limit
+#[derive(Debug)]
+pub struct TooManyRequests {
+    pub limit: u64,
+    /// Seconds until the limit is fully restored
+    pub reset: u64,
+    /// Seconds until the next request is allowed
+    pub retry_after: u64
+}
+impl_basic_err!(TooManyRequests, "TooManyRequests");
+
+impl HttpError for TooManyRequests {
+    fn status(&self) -> status::StatusCode { status::StatusCode::TooManyRequests }
+    fn headers(&self) -> header::Headers {
+        let mut headers = header::Headers::new();
+        headers.set_raw("Retry-After", vec![self.retry_after.to_string().into_bytes()]);
+        headers.set_raw("RateLimit-Limit", vec![self.limit.to_string().into_bytes()]);
+        headers.set_raw("RateLimit-Remaining", vec![b"0".to_vec()]);
+        headers.set_raw("RateLimit-Reset", vec![self.reset.to_string().into_bytes()]);
+        headers
+    }
+    fn public_message(&self) -> Option<String> { Some("Rate limit exceeded".to_string()) }
+}
+
+/// The request body is larger than `Application::max_body_size` once decoded
+#[derive(Debug)]
+pub struct PayloadTooLarge {
+    pub limit: usize
+}
+impl_basic_err!(PayloadTooLarge, "PayloadTooLarge");
+
+impl HttpError for PayloadTooLarge {
+    fn status(&self) -> status::StatusCode { status::StatusCode::PayloadTooLarge }
+    fn public_message(&self) -> Option<String> {
+        Some(format!("Request body exceeds {} bytes", self.limit))
+    }
+}
+
+/// The request body has a `Content-Encoding` that can't be decoded
+#[derive(Debug)]
+pub struct UnsupportedEncoding {
+    pub encoding: String
+}
+impl_basic_err!(UnsupportedEncoding, "UnsupportedEncoding");
+
+impl HttpError for UnsupportedEncoding {
+    fn status(&self) -> status::StatusCode { status::StatusCode::UnsupportedMediaType }
+    fn headers(&self) -> header::Headers {
+        let mut headers = header::Headers::new();
+        headers.set_raw("Accept-Encoding", vec![b"gzip, deflate".to_vec()]);
+        headers
+    }
+    fn public_message(&self) -> Option<String> {
+        Some(format!("Content encoding `{}` is not supported", self.encoding))
+    }
+}
+
+/// The endpoint only accepts requests with an `If-Match` header
+#[derive(Debug)]
+pub struct PreconditionRequired;
+impl_basic_err!(PreconditionRequired, "PreconditionRequired");
+
+impl HttpError for PreconditionRequired {
+    fn status(&self) -> status::StatusCode { status::StatusCode::PreconditionRequired }
+    fn public_message(&self) -> Option<String> { Some("If-Match header is required".to_string()) }
+}
+
+/// `If-Match` doesn't match the current version of the resource
+#[derive(Debug)]
+pub struct PreconditionFailed;
+impl_basic_err!(PreconditionFailed, "PreconditionFailed");
+
+impl HttpError for PreconditionFailed {
+    fn status(&self) -> status::StatusCode { status::StatusCode::PreconditionFailed }
+    fn public_message(&self) -> Option<String> { Some("Resource has been modified".to_string()) }
+}
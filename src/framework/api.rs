@@ -4,7 +4,7 @@ use framework::media;
 use framework::path;
 use backend;
 use errors;
-use server::mime;
+use server::{mime, method};
 use server::header;
 use json::{JsonValue};
 
@@ -31,6 +31,12 @@ pub struct Api {
     before_validation: framework::Callbacks,
     after_validation: framework::Callbacks,
     after: framework::Callbacks,
+    ensure: framework::EnsureCallbacks,
+    response_hooks: framework::ResponseHooks,
+    rescuers: framework::Rescuers,
+    arounds: framework::Arounds,
+    helpers: framework::Helpers,
+    security: framework::Security,
     error_formatters: framework::ErrorFormatters,
     consumes: Option<Vec<mime::Mime>>,
     produces: Option<Vec<mime::Mime>>,
@@ -49,6 +55,12 @@ impl Api {
             before_validation: vec![],
             after_validation: vec![],
             after: vec![],
+            ensure: vec![],
+            response_hooks: vec![],
+            rescuers: vec![],
+            arounds: vec![],
+            helpers: framework::Helpers::new(),
+            security: framework::Security::new(),
             error_formatters: vec![],
             consumes: None,
             produces: None,
@@ -83,12 +95,35 @@ impl Api {
 
     pub fn error_formatter<F: 'static>(&mut self, formatter: F)
     where F: Fn(&errors::Error, &media::Media) -> Option<backend::Response> + Send+Sync {
+        self.error_formatters.push(Box::new(move |err, context| formatter(err, context.media)));
+    }
+
+    pub fn error_formatter_with_context<F: 'static>(&mut self, formatter: F)
+    where F: for<'a> Fn(&errors::Error, &framework::ErrorContext<'a>) -> Option<backend::Response> + Send+Sync {
         self.error_formatters.push(Box::new(formatter));
     }
 
-    fn handle_error(&self, err: &errors::Error, media: &media::Media) -> Option<backend::Response>  {
+    /// Builds the response of an error with the error formatters, unless it already has one
+    pub fn format_error(&self, err_resp: errors::ErrorResponse, req: &backend::Request, app: &framework::Application) -> errors::ErrorResponse {
+        if err_resp.response.is_some() {
+            return err_resp;
+        }
+
+        let media = self.extract_media(req).unwrap_or_else(|| media::Media::default());
+        let resp = self.handle_error(&*err_resp.error, &framework::ErrorContext {
+            media: &media,
+            request: req,
+            app: app
+        });
+        errors::ErrorResponse {
+            error: err_resp.error,
+            response: resp
+        }
+    }
+
+    fn handle_error(&self, err: &errors::Error, context: &framework::ErrorContext) -> Option<backend::Response>  {
         for err_formatter in self.error_formatters.iter() {
-            match err_formatter(err, media) {
+            match err_formatter(err, context) {
                 Some(resp) => return Some(resp),
                 None => ()
             }
@@ -97,6 +132,26 @@ impl Api {
         None
     }
 
+    fn path_template(&self) -> Option<String> {
+        let mut segments = vec![];
+        if let Some(ref prefix) = self.prefix {
+            segments.push(path::normalize(prefix).to_string());
+        }
+        if let Some(Version{ref version, versioning: Versioning::Path}) = self.version {
+            segments.push(version.clone());
+        }
+
+        if segments.is_empty() { None } else { Some(segments.join("/")) }
+    }
+
+    fn api_version(&self) -> Option<&str> {
+        self.version.as_ref().map(|version| &version.version[..])
+    }
+
+    fn error_formatters(&self) -> Option<&framework::ErrorFormatters> {
+        Some(&self.error_formatters)
+    }
+
     fn extract_media(&self, req: &backend::Request) -> Option<media::Media> {
         let header = req.headers().get::<header::Accept>();
         match header {
@@ -194,16 +249,27 @@ impl framework::ApiHandler for Api {
         }
 
         self.push_node(info);
-        self.call_handlers(rest_path, params, req, info).map_err(|err_resp| {
-            if err_resp.response.is_some() {
-                err_resp
+        let app = info.app;
+        self.call_handlers(rest_path, params, req, info).map_err(|err_resp| self.format_error(err_resp, req, app))
+    }
+    fn route_methods(&self, rest_path: &str, methods: &mut Vec<method::Method>) {
+        let mut rest_path = match self.prefix.as_ref() {
+            Some(prefix) if rest_path.starts_with(&prefix[..]) => path::normalize(&rest_path[(prefix.len())..]),
+            Some(_) => return,
+            None => rest_path
+        };
+
+        // Other versioning strategies depend on the request so any version is assumed
+        if let Some(&Version { ref version, versioning: Versioning::Path }) = self.version.as_ref() {
+            if rest_path.starts_with(&version[..]) {
+                rest_path = path::normalize(&rest_path[(version.len())..]);
             } else {
-                let resp = self.handle_error(&*err_resp.error, &self.extract_media(req).unwrap_or_else(|| media::Media::default()));
-                errors::ErrorResponse {
-                    error: err_resp.error,
-                    response: resp
-                }
+                return;
             }
-        })
+        }
+
+        for handler in self.handlers.iter() {
+            handler.route_methods(rest_path, methods);
+        }
     }
 }
@@ -4,11 +4,15 @@ use std::any::TypeId;
 use std::mem;
 
 use backend;
+use server::method;
 use super::{CallInfo};
 use json::{JsonValue};
 
 pub trait ApiHandler: Typeable {
     fn api_call<'a, 'b>(&'a self, &str, &mut JsonValue, &'b mut (backend::Request + 'b), &mut CallInfo<'a>) -> backend::HandleResult<backend::Response>;
+
+    /// Collects the methods of the endpoints whose path matches `rest_path`
+    fn route_methods(&self, _rest_path: &str, _methods: &mut Vec<method::Method>) {}
 }
 
 impl ApiHandler {
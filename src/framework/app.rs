@@ -1,17 +1,33 @@
+use std::any::Any;
 use std::collections;
+use std::io::{self, Read};
+use std::time::Instant;
+use flate2::read::{GzDecoder, ZlibDecoder};
 use typemap;
 use queryst;
+use jsonway;
 
 use super::{ApiHandler};
 use framework::api;
+use framework::client::Client;
+use framework::state;
+use framework::Outcome;
 use backend;
 use errors;
-use server::status;
+use framework::media;
+use framework::{RequestStart, RequestHooks, ResponseHooks};
+use server::{status, header, mime};
 use json::{JsonValue};
 
 pub struct Application {
     pub ext: typemap::TypeMap,
+    pub state: state::State,
     pub root_api: api::Api,
+    http_errors: Vec<errors::HttpErrorCast>,
+    providers: state::Providers,
+    request_hooks: RequestHooks,
+    response_hooks: ResponseHooks,
+    max_body_size: usize
 }
 
 unsafe impl Send for Application {}
@@ -21,21 +37,90 @@ impl Application {
     pub fn new(root_api: api::Api) -> Application {
         Application {
             root_api: root_api,
-            ext: typemap::TypeMap::new()
+            ext: typemap::TypeMap::new(),
+            state: state::State::new(),
+            http_errors: errors::builtin_http_errors(),
+            providers: state::Providers::new(),
+            request_hooks: vec![],
+            response_hooks: vec![],
+            max_body_size: 100 * 1024 * 1024
         }
     }
 
-    fn call_internal<'a>(&self, req: &'a mut (backend::Request + 'a)) -> backend::HandleResult<backend::Response> {
+    /// Largest request body that is parsed, measured both as received and
+    /// after decoding. Defaults to 100 MiB.
+    ///
+    /// Bodies without a content coding are read by the backend first, so the
+    /// `bodyparser::MaxBodyLength` of the iron backend applies to them as well.
+    pub fn max_body_size(&mut self, bytes: usize) {
+        self.max_body_size = bytes;
+    }
+
+    /// Registers a factory of request-scoped values of type `T`, see `Client::inject`.
+    pub fn provide<T: Any, F: 'static>(&mut self, factory: F)
+    where F: for<'a> Fn(&mut Client<'a>) -> backend::HandleResult<T> + Send+Sync {
+        self.providers.register(factory, |_value, _outcome| ());
+    }
+
+    /// Like `provide`, `cleanup` receives the value and the outcome once the request is over.
+    pub fn provide_with_cleanup<T: Any, F: 'static, C: 'static>(&mut self, factory: F, cleanup: C)
+    where F: for<'a> Fn(&mut Client<'a>) -> backend::HandleResult<T> + Send+Sync,
+          C: for<'r> Fn(T, Outcome<'r>) + Send+Sync {
+        self.providers.register(factory, cleanup);
+    }
+
+    /// Runs on every request before it is parsed and routed, in the order of registration
+    pub fn on_request<F: 'static>(&mut self, hook: F) where F: Fn(&mut backend::Request) + Send+Sync {
+        self.request_hooks.push(Box::new(hook));
+    }
+
+    /// Runs on the final response of every request, after the hooks of the
+    /// nodes, in the order of registration
+    pub fn on_response<F: 'static>(&mut self, hook: F) where F: Fn(&backend::Request, &mut backend::Response) + Send+Sync {
+        self.response_hooks.push(Box::new(hook));
+    }
+
+    pub fn providers(&self) -> &state::Providers {
+        &self.providers
+    }
+
+    /// Makes the `HttpError` implementation of `E` known to the application.
+    pub fn http_error<E: errors::HttpError>(&mut self) {
+        self.http_errors.push(errors::http_error_cast::<E>);
+    }
+
+    pub fn find_http_error<'e>(&self, error: &'e errors::Error) -> Option<&'e errors::HttpError> {
+        for cast in self.http_errors.iter() {
+            match cast(error) {
+                Some(http_error) => return Some(http_error),
+                None => ()
+            }
+        }
+
+        None
+    }
+
+    fn call_internal<'a, 'r>(&'a self, req: &'r mut (backend::Request + 'r), info: &mut super::CallInfo<'a>) -> backend::HandleResult<backend::Response> {
         let mut params = JsonValue::Object(collections::BTreeMap::new());
-        let parse_result = parse_request(req, &mut params);
+        info.request_error = parse_request(req, &mut params, self.max_body_size).err();
 
-        parse_result.and_then(|_| {
-            self.root_api.api_call(&(req.url().path().join("/")), &mut params, req, &mut super::CallInfo::new(self))
-        })
+        let result = self.root_api.api_call(&(req.url().path().join("/")), &mut params, req, info);
+
+        // No endpoint raised the error of a malformed request, the root API formats it
+        match (info.request_error.take(), result) {
+            (Some(err_resp), Err(_)) => Err(self.root_api.format_error(err_resp, req, self)),
+            (_, result) => result
+        }
     }
 
     pub fn call<'a>(&self, req: &'a mut (backend::Request + 'a)) -> backend::HandleResultStrict<backend::Response> {
-        self.call_internal(req)
+        req.ext_mut().insert::<RequestStart>(Instant::now());
+        for hook in self.request_hooks.iter() {
+            hook(req);
+        }
+
+        let mut info = super::CallInfo::new(self);
+        let mut result = self.call_internal(req, &mut info)
             .map_err(|error_response| {
                 if error_response.response.is_some() {
                     let errors::ErrorResponse{error, response} = error_response;
@@ -45,28 +130,57 @@ impl Application {
                     }
                 } else {
                     let errors::ErrorResponse{error, ..} = error_response;
-
-                    // Simple default error responses for common errors
-                    let response = if (&*error as &errors::Error).is::<errors::NotMatch>() {
-                        backend::Response::new(
-                            status::StatusCode::NotFound
-                        )
-                    } else if (&*error as &errors::Error).is::<errors::Validation>() {
-                        backend::Response::new(
-                            status::StatusCode::BadRequest
-                        )
-                    } else {
-                        backend::Response::new(
-                            status::StatusCode::InternalServerError
-                        )
-                    };
+                    let response = self.default_error_response(&*error, req);
 
                     errors::StrictErrorResponse {
                         error: error,
                         response: response
                     }
                 }
-            })
+            });
+
+        // The nodes the request was routed through
+        let parents = match result {
+            Err(ref err_resp) if err_resp.error.is::<errors::NotMatch>() && info.deepest_parents.len() > info.parents.len() => &info.deepest_parents,
+            _ => &info.parents
+        };
+        let node_hooks = parents.iter().rev().flat_map(|parent| parent.get_response_hooks().iter());
+        for hook in node_hooks.chain(self.response_hooks.iter()) {
+            match result {
+                Ok(ref mut response) => hook(req, response),
+                Err(ref mut err_resp) => hook(req, &mut err_resp.response)
+            }
+        }
+
+        result
+    }
+}
+
+impl Application {
+    /// Simple default error responses for common errors, used when no error
+    /// formatter describes the error
+    pub fn default_error_response(&self, error: &errors::Error, req: &backend::Request) -> backend::Response {
+        let http_error = self.find_http_error(error);
+        let status = http_error.map_or(status::StatusCode::InternalServerError, |err| err.status());
+
+        let mut response = match http_error.and_then(|err| default_error_body(error, err)) {
+            Some(ref body) if prefers_plain_text(req) => {
+                let mut response = backend::Response::new(status);
+                response.set_header(header::ContentType(
+                    mime::Mime(mime::TopLevel::Text, mime::SubLevel::Plain, vec![])
+                ));
+                response.replace_body(Box::new(plain_text_error_body(body)));
+                response
+            },
+            Some(ref body) => backend::Response::from_json(status, body),
+            None => backend::Response::new(status)
+        };
+
+        if let Some(http_error) = http_error {
+            response.headers.extend(http_error.headers().iter());
+        }
+
+        response
     }
 }
 
@@ -75,6 +189,83 @@ impl super::super::Extensible for Application {
     fn ext_mut(&mut self) -> &mut ::typemap::TypeMap { &mut self.ext }
 }
 
+/// Describes errors with a public message as `{"code", "message", "errors"}` objects
+fn default_error_body(error: &errors::Error, http_error: &errors::HttpError) -> Option<JsonValue> {
+    let message = match http_error.public_message() {
+        Some(message) => message,
+        None => return None
+    };
+
+    Some(jsonway::object(|json| {
+        json.set("code", error_code(error.description()));
+        json.set("message", message);
+        if let Some(validation) = error.downcast::<errors::Validation>() {
+            json.set("errors", validation.to_json());
+        }
+    }).unwrap())
+}
+
+/// Turns error descriptions like `QueryString` into `query_string`
+fn error_code(description: &str) -> String {
+    let mut code = String::new();
+    for (i, ch) in description.chars().enumerate() {
+        if ch.is_uppercase() {
+            if i > 0 {
+                code.push('_');
+            }
+            code.extend(ch.to_lowercase());
+        } else if ch.is_alphanumeric() {
+            code.push(ch);
+        } else if !code.ends_with("_") {
+            code.push('_');
+        }
+    }
+    code
+}
+
+fn plain_text_error_body(body: &JsonValue) -> String {
+    let mut text = body.find("message").and_then(|m| m.as_str()).unwrap_or("").to_string();
+    if let Some(errors) = body.find("errors").and_then(|e| e.as_array()) {
+        for error in errors.iter() {
+            let path = error.find("path").and_then(|p| p.as_str()).unwrap_or("");
+            let message = error.find("message").and_then(|m| m.as_str()).unwrap_or("");
+            text.push_str(&format!("\n{}: {}", path, message));
+        }
+    }
+    text
+}
+
+/// JSON is used unless the client prefers `text/plain` to it. Media types
+/// are weighed by their quality, types we can't produce are skipped.
+fn prefers_plain_text(req: &backend::Request) -> bool {
+    let mut mimes: Vec<&header::QualityItem<mime::Mime>> = match req.headers().get::<header::Accept>() {
+        Some(&header::Accept(ref mimes)) => mimes.iter().filter(|mime| mime.quality > header::Quality(0)).collect(),
+        None => return false
+    };
+    // The sort is stable so equally weighed types keep the order of the client
+    mimes.sort_by(|a, b| b.quality.cmp(&a.quality));
+
+    for mime in mimes.iter() {
+        match (&mime.item, media::Format::from_mime(&mime.item)) {
+            (_, media::Format::PlainTextFormat) => return true,
+            (_, media::Format::JsonFormat) => return false,
+            (&mime::Mime(mime::TopLevel::Star, _, _), _) => return false,
+            (&mime::Mime(mime::TopLevel::Application, mime::SubLevel::Star, _), _) => return false,
+            (&mime::Mime(mime::TopLevel::Text, mime::SubLevel::Star, _), _) => return true,
+            _ => ()
+        }
+    }
+
+    false
+}
+
+#[test]
+fn it_builds_error_codes() {
+    assert_eq!(error_code("Validation"), "validation");
+    assert_eq!(error_code("QueryString"), "query_string");
+    assert_eq!(error_code("Rate limit exceeded"), "rate_limit_exceeded");
+}
+
 fn parse_query(query_str: &str, params: &mut JsonValue) -> backend::HandleSuccessResult {
     let maybe_query_params = queryst::parse(query_str);
     match maybe_query_params {
@@ -94,10 +285,7 @@ fn parse_query(query_str: &str, params: &mut JsonValue) -> backend::HandleSucces
     Ok(())
 }
 
-fn parse_json_body(req: &mut backend::Request, params: &mut JsonValue) -> backend::HandleSuccessResult {
-    let maybe_body = try!(req.read_to_end().map_err(|err| error_response_boxed!(err)))
-        .unwrap_or(String::new());
-
+fn parse_json_body(maybe_body: String, params: &mut JsonValue) -> backend::HandleSuccessResult {
     if maybe_body.len() > 0 {
       let maybe_json_body = maybe_body.parse::<JsonValue>();
         match maybe_json_body {
@@ -120,10 +308,7 @@ fn parse_json_body(req: &mut backend::Request, params: &mut JsonValue) -> backen
     Ok(())
 }
 
-fn parse_urlencoded_body(req: &mut backend::Request, params: &mut JsonValue) -> backend::HandleSuccessResult {
-    let maybe_body = try!(req.read_to_end().map_err(|err| error_response_boxed!(err)))
-        .unwrap_or(String::new());
-
+fn parse_urlencoded_body(maybe_body: String, params: &mut JsonValue) -> backend::HandleSuccessResult {
     if maybe_body.len() > 0 {
         let maybe_json_body = queryst::parse(&maybe_body);
         match maybe_json_body {
@@ -146,7 +331,61 @@ fn parse_urlencoded_body(req: &mut backend::Request, params: &mut JsonValue) ->
     Ok(())
 }
 
-fn parse_request(req: &mut backend::Request, params: &mut JsonValue) -> backend::HandleSuccessResult {
+/// Content codings of the body in the order they were applied
+fn content_encodings(req: &backend::Request) -> Vec<String> {
+    req.headers().get_raw("Content-Encoding")
+        .map(|values| values.iter()
+            .filter_map(|value| String::from_utf8(value.clone()).ok())
+            .flat_map(|value| value.split(',').map(|coding| coding.trim().to_lowercase()).collect::<Vec<String>>())
+            .filter(|coding| !coding.is_empty() && coding != "identity")
+            .collect())
+        .unwrap_or(vec![])
+}
+
+/// Reads at most `limit` bytes, failing when there are more
+fn read_limited<R: io::Read>(reader: R, limit: usize) -> backend::HandleResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    try!(reader.take(limit as u64 + 1).read_to_end(&mut bytes).map_err(|err| {
+        error_response!(errors::Body::new(format!("Body can't be decoded: {}", err)))
+    }));
+
+    if bytes.len() > limit {
+        Err(error_response!(errors::PayloadTooLarge { limit: limit }))
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// Reads the body, decoding gzip and deflate content codings. Decoded bodies
+/// are kept in the request extensions for later reads.
+fn read_body(req: &mut backend::Request, max_body_size: usize) -> backend::HandleResult<String> {
+    let encodings = content_encodings(req);
+    if encodings.is_empty() {
+        let body = try!(req.read_to_end().map_err(|err| error_response_boxed!(err))).unwrap_or(String::new());
+        if body.len() > max_body_size {
+            return Err(error_response!(errors::PayloadTooLarge { limit: max_body_size }));
+        }
+        return Ok(body);
+    }
+
+    if let Some(encoding) = encodings.iter().find(|coding| !["gzip", "x-gzip", "deflate"].contains(&&coding[..])) {
+        return Err(error_response!(errors::UnsupportedEncoding { encoding: encoding.clone() }));
+    }
+
+    let mut bytes = try!(read_limited(req.body_mut(), max_body_size));
+    for encoding in encodings.iter().rev() {
+        bytes = match &encoding[..] {
+            "deflate" => try!(read_limited(ZlibDecoder::new(&bytes[..]), max_body_size)),
+            _ => try!(read_limited(GzDecoder::new(&bytes[..]), max_body_size))
+        };
+    }
+
+    let body = try!(String::from_utf8(bytes).map_err(|_| error_response!(errors::Body::new("Body is not valid UTF-8".to_string()))));
+    req.ext_mut().insert::<backend::BodyKey>(body.clone());
+    Ok(body)
+}
+
+fn parse_request(req: &mut backend::Request, params: &mut JsonValue, max_body_size: usize) -> backend::HandleSuccessResult {
     // extend params with query-string params if any
     if req.url().query().is_some() {
         try!(parse_query(&req.url().query().as_ref().unwrap(), params));
@@ -154,10 +393,10 @@ fn parse_request(req: &mut backend::Request, params: &mut JsonValue) -> backend:
 
     // extend params with json-encoded body params if any
     if req.is_json_body() {
-        try!(parse_json_body(req, params));
+        try!(parse_json_body(try!(read_body(req, max_body_size)), params));
     } else if req.is_urlencoded_body() {
-        try!(parse_urlencoded_body(req, params));
+        try!(parse_urlencoded_body(try!(read_body(req, max_body_size)), params));
     }
 
     Ok(())
-}
\ No newline at end of file
+}
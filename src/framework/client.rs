@@ -1,13 +1,17 @@
 use typemap;
+use std::any::Any;
 use std::env;
 use std::path::Path;
 use std::fs::File;
+use std::mem;
 
 use backend;
 use errors::{self, Error};
 use framework::app;
 use framework::endpoint;
 use framework::media;
+use framework::state;
+use framework::helpers;
 use server::{status, mime, header};
 use json::{JsonValue};
 
@@ -16,12 +20,23 @@ pub struct Client<'a> {
     pub endpoint: &'a endpoint::Endpoint,
     pub request: &'a mut (backend::Request + 'a),
     pub media: &'a media::Media,
+    /// Helpers of the enclosing nodes, innermost first
+    pub helpers: Vec<&'a helpers::Helpers>,
     pub ext: typemap::TypeMap,
     pub response: backend::Response
 }
 
 pub type ClientResult<'a> = backend::HandleResult<Client<'a>>;
 
+/// Extensions of the last dropped client of a request, stored in the request
+/// extensions. The clients the endpoint builds afterwards, for `rescue_from`
+/// handlers and `ensure` callbacks, take them over.
+pub struct ClientExtensions;
+
+impl typemap::Key for ClientExtensions {
+    type Value = typemap::TypeMap;
+}
+
 impl<'a> Client<'a> {
 
     pub fn new<'r>(app: &'a app::Application, endpoint: &'a endpoint::Endpoint,
@@ -31,11 +46,48 @@ impl<'a> Client<'a> {
             endpoint: endpoint,
             request: request,
             media: media,
+            helpers: vec![],
             ext: typemap::TypeMap::new(),
             response: backend::Response::new(status::StatusCode::Ok)
         }
     }
 
+    //
+    // Dependencies
+    //
+
+    /// Application state of type `T`, registered with `app.state.insert(..)`
+    pub fn state<T: Any + Send + Sync>(&self) -> backend::HandleResult<&'a T> {
+        match self.app.state.get::<T>() {
+            Some(value) => Ok(value),
+            None => Err(error_response!(state::missing::<T>()))
+        }
+    }
+
+    /// Request-scoped value of type `T`, built by its provider on first use
+    pub fn inject<T: Any>(&mut self) -> backend::HandleResult<&mut T> {
+        let app = self.app;
+        app.providers().resolve::<T>(self)
+    }
+
+    /// Calls the innermost helper named `name`
+    pub fn helper(&mut self, name: &str, args: &JsonValue) -> backend::HandleResult<JsonValue> {
+        let helper = self.helpers.iter().filter_map(|helpers| helpers.find_named(name)).next();
+        match helper {
+            Some(helper) => helper(self, args),
+            None => Err(error_response!(errors::MissingHelper { name: name.to_string() }))
+        }
+    }
+
+    /// The innermost helper object of type `T`
+    pub fn helpers<T: Any + Send + Sync>(&self) -> backend::HandleResult<&'a T> {
+        let found = self.helpers.iter().filter_map(|helpers| helpers.find::<T>()).next();
+        match found {
+            Some(found) => Ok(found),
+            None => Err(error_response!(helpers::missing::<T>()))
+        }
+    }
+
     //
     // Work with status
     //
@@ -138,8 +190,27 @@ impl<'a> Client<'a> {
         Ok(self)
     }
 
-    pub fn move_response(self) -> backend::Response {
-        self.response
+    pub fn move_response(mut self) -> backend::Response {
+        mem::replace(&mut self.response, backend::Response::new(status::StatusCode::Ok))
+    }
+
+    /// Another client of the same request taking over the extensions and the
+    /// response of this one, see `Next::run_borrowed`
+    pub fn reborrow<'b>(&'b mut self) -> Client<'b> {
+        Client {
+            app: self.app,
+            endpoint: self.endpoint,
+            request: &mut *self.request,
+            media: self.media,
+            helpers: self.helpers.clone(),
+            ext: mem::replace(&mut self.ext, typemap::TypeMap::new()),
+            response: mem::replace(&mut self.response, backend::Response::new(status::StatusCode::Ok))
+        }
+    }
+
+    pub fn into_parts(mut self) -> (typemap::TypeMap, backend::Response) {
+        (mem::replace(&mut self.ext, typemap::TypeMap::new()),
+         mem::replace(&mut self.response, backend::Response::new(status::StatusCode::Ok)))
     }
 
     pub fn ext(&self) -> &typemap::TypeMap {
@@ -152,6 +223,17 @@ impl<'a> Client<'a> {
 
 }
 
+/// The extensions outlive the client even when a handler or a middleware
+/// drops it on error
+impl<'a> Drop for Client<'a> {
+    fn drop(&mut self) {
+        if !self.ext.is_empty() {
+            let ext = mem::replace(&mut self.ext, typemap::TypeMap::new());
+            self.request.ext_mut().insert::<ClientExtensions>(ext);
+        }
+    }
+}
+
 impl<'a> ::Extensible for Client<'a> {
     fn ext(&self) -> &::typemap::TypeMap { &self.ext }
     fn ext_mut(&mut self) -> &mut ::typemap::TypeMap { &mut self.ext }
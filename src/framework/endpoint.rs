@@ -1,3 +1,5 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
 use valico::json_dsl;
 use valico::json_schema;
 
@@ -21,12 +23,21 @@ pub type EndpointBuilder = FnOnce(&mut Endpoint) -> EndpointHandlerPresent + 'st
 pub struct Endpoint {
     pub method: method::Method,
     pub path: path::Path,
+    pub name: Option<String>,
     pub summary: Option<String>,
     pub desc: Option<String>,
     pub coercer: Option<json_dsl::Builder>,
     pub consumes: Option<Vec<mime::Mime>>,
     pub produces: Option<Vec<mime::Mime>>,
+    pub security: framework::Security,
     handler: Option<EndpointHandler>,
+    before: framework::Callbacks,
+    before_validation: framework::Callbacks,
+    after_validation: framework::Callbacks,
+    after: framework::Callbacks,
+    rescuers: framework::Rescuers,
+    arounds: framework::Arounds,
+    handler_arounds: framework::Arounds,
 }
 
 unsafe impl Send for Endpoint {}
@@ -37,12 +48,21 @@ impl Endpoint {
         Endpoint {
             method: method,
             path: path::Path::parse(path, true).unwrap(),
+            name: None,
             summary: None,
             desc: None,
             coercer: None,
             consumes: None,
             produces: None,
+            security: framework::Security::new(),
             handler: None,
+            before: vec![],
+            before_validation: vec![],
+            after_validation: vec![],
+            after: vec![],
+            rescuers: vec![],
+            arounds: vec![],
+            handler_arounds: vec![],
         }
     }
 
@@ -54,6 +74,11 @@ impl Endpoint {
         endpoint
     }
 
+    /// Names the endpoint so callback filters can refer to it
+    pub fn name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
     pub fn summary(&mut self, summary: &str) {
         self.summary = Some(summary.to_string());
     }
@@ -85,7 +110,61 @@ impl Endpoint {
         EndpointHandlerPresent::HandlerPresent
     }
 
-    fn validate(&self, params: &mut JsonValue, scope: Option<&json_schema::Scope>) -> backend::HandleResult<()> {
+    /// Requires the principal to be granted every one of `scopes`, in addition
+    /// to the scopes required by the parent nodes
+    pub fn requires_scopes(&mut self, scopes: &[&str]) {
+        self.security.scopes.extend(framework::security::to_strings(scopes));
+    }
+
+    pub fn requires_roles(&mut self, roles: &[&str]) {
+        self.security.roles.extend(framework::security::to_strings(roles));
+    }
+
+    /// Runs after the `before` callbacks of the parent nodes
+    pub fn before<F: 'static>(&mut self, callback: F) where F: for<'a> Fn(&'a mut framework::Client, &JsonValue)
+    -> backend::HandleSuccessResult + Send+Sync {
+        self.before.push(Box::new(callback));
+    }
+
+    pub fn before_validation<F: 'static>(&mut self, callback: F) where F: for<'a> Fn(&'a mut framework::Client, &JsonValue)
+    -> backend::HandleSuccessResult + Send+Sync {
+        self.before_validation.push(Box::new(callback));
+    }
+
+    pub fn after_validation<F: 'static>(&mut self, callback: F) where F: for<'a> Fn(&'a mut framework::Client, &JsonValue)
+    -> backend::HandleSuccessResult + Send+Sync {
+        self.after_validation.push(Box::new(callback));
+    }
+
+    pub fn after<F: 'static>(&mut self, callback: F) where F: for<'a> Fn(&'a mut framework::Client, &JsonValue)
+    -> backend::HandleSuccessResult + Send+Sync {
+        self.after.push(Box::new(callback));
+    }
+
+    /// Wraps execution of this endpoint. Runs inside the middleware of the parent nodes.
+    pub fn around<F: 'static>(&mut self, middleware: F)
+    where F: for<'a, 'n> Fn(framework::Client<'a>, &mut JsonValue, framework::Next<'n>) -> framework::client::ClientResult<'a> + Send+Sync {
+        self.around_middleware(middleware);
+    }
+
+    pub fn around_middleware<M: framework::Around + 'static>(&mut self, middleware: M) {
+        self.arounds.push(Box::new(middleware));
+    }
+
+    /// Wraps the handler alone, after the callbacks and the validation and
+    /// before the `after` callbacks. The first registered middleware is the outermost.
+    pub fn around_handler<M: framework::Around + 'static>(&mut self, middleware: M) {
+        self.handler_arounds.push(Box::new(middleware));
+    }
+
+    /// Handles errors of type `E` raised by this endpoint. Takes precedence over
+    /// handlers registered on the parent nodes.
+    pub fn rescue_from<E: errors::Error, F: 'static>(&mut self, handler: F)
+    where F: for<'a> Fn(&E, framework::Client<'a>) -> framework::client::ClientResult<'a> + Sync+Send {
+        self.rescuers.push(framework::Rescuer::new(handler));
+    }
+
+    fn validate(&self, req: &backend::Request, params: &mut JsonValue, scope: Option<&json_schema::Scope>) -> backend::HandleResult<()> {
         // Validate namespace params with valico
         if self.coercer.is_some() {
             // validate and coerce params
@@ -95,7 +174,7 @@ impl Endpoint {
                 Ok(())
             } else {
                 if state.missing.len() > 0 {
-                    warn!("There are some missing JSON schemes: {:?}", state.missing);
+                    warn!("{}There are some missing JSON schemes: {:?}", framework::log_prefix(req), state.missing);
                 }
                 Err(error_response!(errors::Validation{ reason: state.errors }))
             }
@@ -109,30 +188,216 @@ impl Endpoint {
         req: &'a mut (backend::Request + 'a),
         info: &mut framework::CallInfo) -> backend::HandleResult<backend::Response> {
 
-        let mut client = framework::Client::new(info.app, self, req, &info.media);
+        let matched_route = framework::MatchedRoute {
+            method: self.method.clone(),
+            route: info.route(self),
+            version: info.version().map(|version| version.to_string())
+        };
+        req.ext_mut().insert::<framework::MatchedRoute>(matched_route);
+
+        // Panics of the handler, the callbacks, the rescue handlers and the
+        // error formatters become `errors::Panic`
+        let result = catch_panic(|| {
+            let result = match info.request_error.take() {
+                Some(err_resp) => Err(err_resp),
+                None => self.execute(params, req, info)
+            };
+
+            let result = result.or_else(|err_resp| self.rescue(err_resp, req, info));
+            result.map_err(|err_resp| self.format_error(err_resp, req, info))
+        });
+        let result = result.unwrap_or_else(|message| {
+            let route = info.route(self);
+            error!("{}Panic while handling {} {}: {}", framework::log_prefix(req), self.method, route, message);
+            Err(self.format_panic(errors::Panic { message: message, route: route }, req, info))
+        });
+
+        self.call_ensure(&result, params, req, info);
+
+        // The outcome is kept when the cleanup panics
+        let cleanup = catch_panic(|| {
+            info.app.providers().cleanup(req, result.as_ref());
+        });
+        if let Err(message) = cleanup {
+            error!("{}Panic after handling {} {}: {}", framework::log_prefix(req), self.method, info.route(self), message);
+        }
+
+        result
+    }
+
+    /// Runs every `ensure` callback, a panicking callback doesn't prevent the others from running
+    fn call_ensure<'a>(&self,
+        result: &backend::HandleResult<backend::Response>,
+        params: &JsonValue,
+        req: &'a mut (backend::Request + 'a),
+        info: &framework::CallInfo) {
+
+        let mut ensure = info.parents.iter().rev().flat_map(|parent| parent.get_ensure().iter()).peekable();
+        if ensure.peek().is_none() {
+            return;
+        }
+
+        let log_prefix = framework::log_prefix(req);
+        let mut client = self.client(req, info);
+        for cb in ensure {
+            if let Err(message) = catch_panic(|| cb(&mut client, params, result.as_ref())) {
+                error!("{}Panic in ensure callback of {} {}: {}", log_prefix, self.method, info.route(self), message);
+            }
+        }
+    }
+
+    /// Builds the response of an error with the error formatters of the
+    /// innermost API that has one for it, or with the default one
+    fn format_error(&self, err_resp: errors::ErrorResponse, req: &backend::Request, info: &framework::CallInfo) -> errors::ErrorResponse {
+        if err_resp.response.is_some() {
+            return err_resp;
+        }
+
+        let response = {
+            let context = framework::ErrorContext {
+                media: &info.media,
+                request: req,
+                app: info.app
+            };
+            let error = &*err_resp.error as &errors::Error;
+            info.parents.iter().rev()
+                .filter_map(|parent| parent.get_error_formatters())
+                .flat_map(|formatters| formatters.iter())
+                .filter_map(|formatter| formatter(error, &context))
+                .next()
+                .unwrap_or_else(|| info.app.default_error_response(error, req))
+        };
+
+        errors::ErrorResponse {
+            error: err_resp.error,
+            response: Some(response)
+        }
+    }
+
+    /// Formats a panic, falling back to the default response when a formatter panics too
+    fn format_panic(&self, panic: errors::Panic, req: &backend::Request, info: &framework::CallInfo) -> errors::ErrorResponse {
+        let formatted = catch_panic(|| {
+            self.format_error(error_response!(errors::Panic { message: panic.message.clone(), route: panic.route.clone() }), req, info)
+        });
+
+        formatted.unwrap_or_else(|_| {
+            let response = info.app.default_error_response(&panic, req);
+            errors::ErrorResponse {
+                error: Box::new(panic),
+                response: Some(response)
+            }
+        })
+    }
+
+    fn execute<'a>(&self,
+        params: &mut JsonValue,
+        req: &'a mut (backend::Request + 'a),
+        info: &framework::CallInfo) -> backend::HandleResult<backend::Response> {
+
+        let client = self.client(req, info);
+
+        let arounds: Vec<&framework::Around> = info.parents.iter()
+            .flat_map(|parent| parent.get_arounds().iter())
+            .chain(self.arounds.iter())
+            .map(|around| &**around)
+            .collect();
+
+        let stages = continuation(|client, params| self.run_stages(client, params, info));
+        let client = try!(framework::Next::new(&arounds, &stages).run(client, params));
+
+        Ok(client.move_response())
+    }
+
+    /// Callbacks, validation and the handler itself
+    fn run_stages<'a>(&self,
+        mut client: framework::Client<'a>,
+        params: &mut JsonValue,
+        info: &framework::CallInfo) -> framework::client::ClientResult<'a> {
 
         for parent in info.parents.iter() {
             try!(Endpoint::call_callbacks(parent.get_before(), &mut client, params));
         }
+        try!(Endpoint::call_callbacks(&self.before, &mut client, params));
+
+        try!(self.authorize(&client, info));
 
         for parent in info.parents.iter() {
             try!(Endpoint::call_callbacks(parent.get_before_validation(), &mut client, params));
         }
+        try!(Endpoint::call_callbacks(&self.before_validation, &mut client, params));
 
-        try!(self.validate(params, info.app.ext.get::<schemes::SchemesScope>()));
+        try!(self.validate(client.request, params, info.app.ext.get::<schemes::SchemesScope>()));
 
         for parent in info.parents.iter() {
             try!(Endpoint::call_callbacks(parent.get_after_validation(), &mut client, params));
         }
+        try!(Endpoint::call_callbacks(&self.after_validation, &mut client, params));
 
-        let handler = self.handler.as_ref();
-        let mut client = try!((handler.unwrap())(client, params));
+        let handler = self.handler.as_ref().unwrap();
+        let handler_arounds: Vec<&framework::Around> = self.handler_arounds.iter().map(|around| &**around).collect();
+        let handle = continuation(|client, params| handler(client, params));
+        let mut client = try!(framework::Next::new(&handler_arounds, &handle).run(client, params));
 
         for parent in info.parents.iter() {
             try!(Endpoint::call_callbacks(parent.get_after(), &mut client, params));
         }
+        try!(Endpoint::call_callbacks(&self.after, &mut client, params));
 
-        Ok(client.move_response())
+        Ok(client)
+    }
+
+    /// Checks the scopes and roles required by the endpoint and its parents
+    fn authorize(&self, client: &framework::Client, info: &framework::CallInfo) -> backend::HandleSuccessResult {
+        let principal = client.ext.get::<framework::security::PrincipalKey>();
+        let requirements = info.parents.iter().map(|parent| parent.get_security()).chain(Some(&self.security));
+        for security in requirements {
+            if let Err(err) = security.authorize(principal) {
+                return Err(error_response!(err));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Passes the error to the innermost matching `rescue_from` handler
+    fn rescue<'a>(&self,
+        err_resp: errors::ErrorResponse,
+        req: &'a mut (backend::Request + 'a),
+        info: &framework::CallInfo) -> backend::HandleResult<backend::Response> {
+
+        if err_resp.response.is_some() {
+            return Err(err_resp);
+        }
+
+        let rescuer = {
+            let error = &*err_resp.error as &errors::Error;
+            self.rescuers.iter()
+                .chain(info.parents.iter().rev().flat_map(|parent| parent.get_rescuers().iter()))
+                .find(|rescuer| rescuer.handles(error))
+        };
+
+        match rescuer {
+            Some(rescuer) => {
+                let client = self.client(req, info);
+                let client = try!((rescuer.handler)(&*err_resp.error, client));
+                Err(errors::ErrorResponse {
+                    error: err_resp.error,
+                    response: Some(client.move_response())
+                })
+            },
+            None => Err(err_resp)
+        }
+    }
+
+    /// A client of the request, with the extensions of the previous client of the endpoint if any
+    fn client<'c>(&'c self, req: &'c mut (backend::Request + 'c), info: &'c framework::CallInfo) -> framework::Client<'c> {
+        let ext = req.ext_mut().remove::<framework::client::ClientExtensions>();
+        let mut client = framework::Client::new(info.app, self, req, &info.media);
+        if let Some(ext) = ext {
+            client.ext = ext;
+        }
+        client.helpers = info.parents.iter().rev().map(|parent| parent.get_helpers()).collect();
+        client
     }
 
     fn call_callbacks(
@@ -149,6 +414,26 @@ impl Endpoint {
 
 }
 
+/// Makes the closure generic over the lifetime of the client
+fn continuation<F>(f: F) -> F where F: for<'a> Fn(framework::Client<'a>, &mut JsonValue) -> framework::client::ClientResult<'a> {
+    f
+}
+
+/// Runs `f`, returning the message of the panic it raised, if any
+fn catch_panic<T, F: FnOnce() -> T>(f: F) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| panic_message(&payload))
+}
+
+fn panic_message(payload: &Box<Any + Send>) -> String {
+    match payload.downcast_ref::<&'static str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "Box<Any>".to_string()
+        }
+    }
+}
+
 impl framework::ApiHandler for Endpoint {
     fn api_call<'r>(&self,
         rest_path: &str,
@@ -170,4 +455,10 @@ impl framework::ApiHandler for Endpoint {
         }
 
     }
+
+    fn route_methods(&self, rest_path: &str, methods: &mut Vec<method::Method>) {
+        if self.path.is_match(rest_path).is_some() && !methods.contains(&self.method) {
+            methods.push(self.method.clone());
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+use backend;
+use framework;
+use framework::client::Client;
+use framework::endpoint::Endpoint;
+use framework::path;
+use json::{JsonValue};
+use server::method::Method;
+
+/// Selects endpoints by name, method or path
+pub enum EndpointMatcher {
+    Name(String),
+    Method(Method),
+    Path(String)
+}
+
+impl EndpointMatcher {
+    pub fn name(name: &str) -> EndpointMatcher {
+        EndpointMatcher::Name(name.to_string())
+    }
+
+    pub fn method(method: Method) -> EndpointMatcher {
+        EndpointMatcher::Method(method)
+    }
+
+    pub fn path(path: &str) -> EndpointMatcher {
+        EndpointMatcher::Path(path::normalize(path).to_string())
+    }
+
+    pub fn matches(&self, endpoint: &Endpoint) -> bool {
+        match self {
+            &EndpointMatcher::Name(ref name) => endpoint.name.as_ref() == Some(name),
+            &EndpointMatcher::Method(ref method) => &endpoint.method == method,
+            &EndpointMatcher::Path(ref path) => path::normalize(&endpoint.path.path) == &path[..]
+        }
+    }
+}
+
+/// Restricts a callback to some of the endpoints it applies to
+pub enum CallbackFilter {
+    Only(Vec<EndpointMatcher>),
+    Except(Vec<EndpointMatcher>)
+}
+
+impl CallbackFilter {
+    pub fn only(matchers: Vec<EndpointMatcher>) -> CallbackFilter {
+        CallbackFilter::Only(matchers)
+    }
+
+    pub fn except(matchers: Vec<EndpointMatcher>) -> CallbackFilter {
+        CallbackFilter::Except(matchers)
+    }
+
+    pub fn allows(&self, endpoint: &Endpoint) -> bool {
+        match self {
+            &CallbackFilter::Only(ref matchers) => matchers.iter().any(|m| m.matches(endpoint)),
+            &CallbackFilter::Except(ref matchers) => !matchers.iter().any(|m| m.matches(endpoint))
+        }
+    }
+
+    /// Makes a callback that only runs for the endpoints this filter allows
+    pub fn wrap<F: 'static>(self, callback: F) -> framework::Callback
+    where F: for<'a> Fn(&'a mut Client, &JsonValue) -> backend::HandleSuccessResult + Send+Sync {
+        Box::new(move |client, params| {
+            if self.allows(client.endpoint) {
+                callback(client, params)
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use std::any::{self, Any};
+use std::collections::HashMap;
+use typemap;
+
+use backend;
+use errors;
+use framework::client::Client;
+use framework::state::Entry;
+use json::{JsonValue};
+
+pub type Helper = Box<for<'a> Fn(&mut Client<'a>, &JsonValue) -> backend::HandleResult<JsonValue> + 'static + Sync + Send>;
+
+/// Helper functions and objects shared by the endpoints of an `Api` or a `Namespace`.
+pub struct Helpers {
+    named: HashMap<String, Helper>,
+    typed: typemap::ShareMap
+}
+
+impl Helpers {
+    pub fn new() -> Helpers {
+        Helpers {
+            named: HashMap::new(),
+            typed: typemap::ShareMap::custom()
+        }
+    }
+
+    pub fn insert_named(&mut self, name: &str, helper: Helper) {
+        self.named.insert(name.to_string(), helper);
+    }
+
+    pub fn insert<T: Any + Send + Sync>(&mut self, helpers: T) {
+        self.typed.insert::<Entry<T>>(helpers);
+    }
+
+    pub fn find_named(&self, name: &str) -> Option<&Helper> {
+        self.named.get(name)
+    }
+
+    pub fn find<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.typed.get::<Entry<T>>()
+    }
+}
+
+pub fn missing<T: Any>() -> errors::MissingHelper {
+    errors::MissingHelper {
+        name: any::type_name::<T>().to_string()
+    }
+}
@@ -0,0 +1,69 @@
+use backend;
+use framework::client::{Client, ClientResult, ClientExtensions};
+use json::{JsonValue};
+
+/// Middleware that wraps the execution of an endpoint.
+///
+/// The whole endpoint pipeline (callbacks, validation and the handler) runs
+/// when `next.run` is called. A middleware may skip it to short-circuit with
+/// its own response, call it again, or inspect and transform what comes back.
+///
+/// `rescue_from` handlers run outside of the middleware, which sees the
+/// errors before they are rescued.
+pub trait Around: Send + Sync {
+    fn call<'a, 'n>(&self, client: Client<'a>, params: &mut JsonValue, next: Next<'n>) -> ClientResult<'a>;
+}
+
+impl<F> Around for F
+where F: for<'a, 'n> Fn(Client<'a>, &mut JsonValue, Next<'n>) -> ClientResult<'a> + Send + Sync {
+    fn call<'a, 'n>(&self, client: Client<'a>, params: &mut JsonValue, next: Next<'n>) -> ClientResult<'a> {
+        self(client, params, next)
+    }
+}
+
+pub type Arounds = Vec<Box<Around>>;
+
+pub type Continuation<'n> = &'n (for<'a> Fn(Client<'a>, &mut JsonValue) -> ClientResult<'a> + 'n);
+
+/// The rest of the middleware chain followed by the endpoint itself
+#[derive(Clone, Copy)]
+pub struct Next<'n> {
+    chain: &'n [&'n Around],
+    endpoint: Continuation<'n>
+}
+
+impl<'n> Next<'n> {
+    pub fn new(chain: &'n [&'n Around], endpoint: Continuation<'n>) -> Next<'n> {
+        Next {
+            chain: chain,
+            endpoint: endpoint
+        }
+    }
+
+    pub fn run<'a>(&self, client: Client<'a>, params: &mut JsonValue) -> ClientResult<'a> {
+        match self.chain.split_first() {
+            Some((around, rest)) => around.call(client, params, Next::new(rest, self.endpoint)),
+            None => (self.endpoint)(client, params)
+        }
+    }
+
+    /// Like `run`, but `client` stays usable when the rest of the chain fails,
+    /// e.g. to run it again. The extensions and the response of `client` are
+    /// lent to the run and given back, a failed run only gives back the extensions.
+    pub fn run_borrowed<'a>(&self, client: &mut Client<'a>, params: &mut JsonValue) -> backend::HandleSuccessResult {
+        let result = self.run(client.reborrow(), params).map(|run| run.into_parts());
+        match result {
+            Ok((ext, response)) => {
+                client.ext = ext;
+                client.response = response;
+                Ok(())
+            },
+            Err(err_resp) => {
+                if let Some(ext) = client.request.ext_mut().remove::<ClientExtensions>() {
+                    client.ext = ext;
+                }
+                Err(err_resp)
+            }
+        }
+    }
+}
@@ -1,6 +1,11 @@
+use std::any::TypeId;
+use std::time::Instant;
+use typemap;
+
 use backend;
 use errors;
 use json::{JsonValue};
+use server::method;
 
 pub use self::api_handler::{ApiHandler, ApiHandlers};
 pub use self::app::{Application};
@@ -11,6 +16,11 @@ pub use self::nesting::{Nesting, Node};
 pub use self::namespace::{Namespace};
 pub use self::media::Media;
 pub use self::path::Path;
+pub use self::middleware::{Around, Arounds, Next};
+pub use self::filter::{CallbackFilter, EndpointMatcher};
+pub use self::state::{State, Providers};
+pub use self::helpers::{Helper, Helpers};
+pub use self::security::{Security, SecurityScheme, Principal};
 
 #[macro_use]
 pub mod nesting;
@@ -22,27 +32,139 @@ pub mod client;
 pub mod media;
 pub mod path;
 pub mod app;
+pub mod middleware;
+pub mod filter;
+pub mod state;
+pub mod helpers;
+pub mod security;
 
 pub struct CallInfo<'a> {
     pub media: media::Media,
     pub parents: Vec<&'a (nesting::Node + 'static)>,
-    pub app: &'a app::Application
+    /// The nodes of the deepest branch the path led to, the path is not
+    /// found within them when no endpoint matches it
+    pub deepest_parents: Vec<&'a (nesting::Node + 'static)>,
+    pub app: &'a app::Application,
+    /// The query string or the body can't be parsed, or the params are invalid
+    /// for a namespace. The error is raised by the endpoint the request is routed to.
+    pub request_error: Option<errors::ErrorResponse>
 }
 
 pub type Callback = Box<for<'a> Fn(&'a mut client::Client, &JsonValue) -> backend::HandleSuccessResult + 'static + Sync + Send>;
 pub type Callbacks = Vec<Callback>;
 
-pub type ErrorFormatter = Box<Fn(&errors::Error, &media::Media) -> Option<backend::Response> + 'static + Sync + Send>;
+/// How the endpoint execution ended. The response of errors is already
+/// built by the error formatters when it is passed to `ensure` callbacks.
+pub type Outcome<'r> = Result<&'r backend::Response, &'r errors::ErrorResponse>;
+
+/// Runs before the request is parsed and routed
+pub type RequestHook = Box<Fn(&mut backend::Request) + 'static + Sync + Send>;
+pub type RequestHooks = Vec<RequestHook>;
+
+/// Adjusts the final response of a request, error responses included
+pub type ResponseHook = Box<Fn(&backend::Request, &mut backend::Response) + 'static + Sync + Send>;
+pub type ResponseHooks = Vec<ResponseHook>;
+
+pub type EnsureCallback = Box<for<'a, 'r> Fn(&'a mut client::Client, &JsonValue, Outcome<'r>) + 'static + Sync + Send>;
+pub type EnsureCallbacks = Vec<EnsureCallback>;
+
+/// What an error formatter knows about the request that failed.
+pub struct ErrorContext<'a> {
+    pub media: &'a media::Media,
+    pub request: &'a (backend::Request + 'a),
+    pub app: &'a app::Application
+}
+
+pub type ErrorFormatter = Box<for<'a> Fn(&errors::Error, &ErrorContext<'a>) -> Option<backend::Response> + 'static + Sync + Send>;
 pub type ErrorFormatters = Vec<ErrorFormatter>;
 
+pub type RescueHandler = Box<for<'a> Fn(&errors::Error, client::Client<'a>) -> client::ClientResult<'a> + 'static + Sync + Send>;
+
+/// Handles errors of a single concrete type
+pub struct Rescuer {
+    pub error_type: TypeId,
+    pub handler: RescueHandler
+}
+
+pub type Rescuers = Vec<Rescuer>;
+
+impl Rescuer {
+    pub fn new<E: errors::Error, F: 'static>(handler: F) -> Rescuer
+    where F: for<'a> Fn(&E, client::Client<'a>) -> client::ClientResult<'a> + Sync+Send {
+        Rescuer {
+            error_type: TypeId::of::<E>(),
+            handler: Box::new(move |err, client| {
+                handler(err.downcast::<E>().expect("Rescuer is called with error of a wrong type"), client)
+            })
+        }
+    }
+
+    pub fn handles(&self, err: &errors::Error) -> bool {
+        err.get_type() == self.error_type
+    }
+}
+
 impl<'a> CallInfo<'a> {
     pub fn new(app: &'a Application) -> CallInfo<'a> {
         CallInfo {
             media: Media::default(),
             parents: vec![],
-            app: app
+            deepest_parents: vec![],
+            app: app,
+            request_error: None
         }
     }
+
+    /// Route template of the endpoint within the matched tree, e.g. `/api/users/:user_id`
+    pub fn route(&self, endpoint: &endpoint::Endpoint) -> String {
+        let mut segments: Vec<String> = self.parents.iter()
+            .filter_map(|parent| parent.get_path_template())
+            .collect();
+
+        let endpoint_path = path::normalize(&endpoint.path.path);
+        if !endpoint_path.is_empty() {
+            segments.push(endpoint_path.to_string());
+        }
+
+        format!("/{}", segments.join("/"))
+    }
+
+    /// Version of the innermost versioned API within the matched tree
+    pub fn version(&self) -> Option<&str> {
+        self.parents.iter().rev().filter_map(|parent| parent.get_version()).next()
+    }
+}
+
+/// The endpoint that handled a request, stored in the request extensions
+#[derive(Clone, Debug)]
+pub struct MatchedRoute {
+    pub method: method::Method,
+    /// Route template, e.g. `/api/users/:user_id`
+    pub route: String,
+    pub version: Option<String>
+}
+
+impl typemap::Key for MatchedRoute {
+    type Value = MatchedRoute;
+}
+
+/// When the application started handling the request, stored in the request extensions
+pub struct RequestStart;
+
+impl typemap::Key for RequestStart {
+    type Value = Instant;
+}
+
+/// Prefix of the log lines emitted while handling the request, e.g. the
+/// request id. Request hooks can store it in the request extensions.
+pub struct LogPrefix;
+
+impl typemap::Key for LogPrefix {
+    type Value = String;
+}
+
+pub fn log_prefix(req: &backend::Request) -> String {
+    req.ext().get::<LogPrefix>().cloned().unwrap_or(String::new())
 }
 
 
@@ -2,6 +2,7 @@ use valico::json_dsl;
 use valico::json_schema;
 
 use backend;
+use server::method;
 use errors;
 use json::{JsonValue};
 use framework::nesting::{self, Nesting, Node};
@@ -17,7 +18,13 @@ pub struct Namespace {
     before: framework::Callbacks,
     before_validation: framework::Callbacks,
     after_validation: framework::Callbacks,
-    after: framework::Callbacks
+    after: framework::Callbacks,
+    ensure: framework::EnsureCallbacks,
+    response_hooks: framework::ResponseHooks,
+    rescuers: framework::Rescuers,
+    arounds: framework::Arounds,
+    helpers: framework::Helpers,
+    security: framework::Security
 }
 
 impl_nesting!(Namespace);
@@ -32,7 +39,13 @@ impl Namespace {
             before: vec![],
             before_validation: vec![],
             after_validation: vec![],
-            after: vec![]
+            after: vec![],
+            ensure: vec![],
+            response_hooks: vec![],
+            rescuers: vec![],
+            arounds: vec![],
+            helpers: framework::Helpers::new(),
+            security: framework::Security::new()
         }
     }
 
@@ -47,7 +60,20 @@ impl Namespace {
         return namespace;
     }
 
-    fn validate(&self, params: &mut JsonValue, scope: Option<&json_schema::Scope>) -> backend::HandleResult<()> {
+    fn path_template(&self) -> Option<String> {
+        let path = path::normalize(&self.path.path);
+        if path.is_empty() { None } else { Some(path.to_string()) }
+    }
+
+    fn api_version(&self) -> Option<&str> {
+        None
+    }
+
+    fn error_formatters(&self) -> Option<&framework::ErrorFormatters> {
+        None
+    }
+
+    fn validate(&self, req: &backend::Request, params: &mut JsonValue, scope: Option<&json_schema::Scope>) -> backend::HandleResult<()> {
         // Validate namespace params with valico
         if self.coercer.is_some() {
             // validate and coerce params
@@ -58,7 +84,7 @@ impl Namespace {
                 Ok(())
             } else {
                 if state.missing.len() > 0 {
-                    warn!("There are some missing JSON schemes: {:?}", state.missing);
+                    warn!("{}There are some missing JSON schemes: {:?}", framework::log_prefix(req), state.missing);
                 }
                 Err(error_response!(errors::Validation{ reason: state.errors }))
             }
@@ -86,9 +112,30 @@ impl framework::ApiHandler for Namespace {
             None => return Err(error_response!(errors::NotMatch))
         };
 
-        try!(self.validate(params, info.app.ext.get::<schemes::SchemesScope>()));
+        // Params of malformed requests are incomplete, the endpoint reports the request error instead
+        let mut invalid = false;
+        if info.request_error.is_none() {
+            if let Err(err_resp) = self.validate(req, params, info.app.ext.get::<schemes::SchemesScope>()) {
+                // Raised by the endpoint so that its `ensure` callbacks see the failure
+                info.request_error = Some(err_resp);
+                invalid = true;
+            }
+        }
 
         self.push_node(info);
-        self.call_handlers(rest_path, params, req, info)
+        let result = self.call_handlers(rest_path, params, req, info);
+        match (invalid, result) {
+            (true, Err(_)) if info.request_error.is_some() => Err(info.request_error.take().unwrap()),
+            (_, result) => result
+        }
+    }
+    fn route_methods(&self, rest_path: &str, methods: &mut Vec<method::Method>) {
+        if let Some(captures) = self.path.is_match(rest_path) {
+            let captured_length = captures.at(0).map_or(0, |c| c.len());
+            let rest_path = path::normalize(&rest_path[(captured_length)..]);
+            for handler in self.handlers.iter() {
+                handler.route_methods(rest_path, methods);
+            }
+        }
     }
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+use std::any::Any;
+
 use framework;
 use framework::namespace;
 use framework::endpoint;
@@ -23,6 +25,33 @@ pub trait Node {
     fn get_after<'a>(&'a self) -> &'a framework::Callbacks;
     fn get_after_mut<'a>(&'a mut self) -> &'a mut framework::Callbacks;
 
+    fn get_ensure<'a>(&'a self) -> &'a framework::EnsureCallbacks;
+    fn get_ensure_mut<'a>(&'a mut self) -> &'a mut framework::EnsureCallbacks;
+
+    fn get_response_hooks<'a>(&'a self) -> &'a framework::ResponseHooks;
+    fn get_response_hooks_mut<'a>(&'a mut self) -> &'a mut framework::ResponseHooks;
+
+    fn get_rescuers<'a>(&'a self) -> &'a framework::Rescuers;
+    fn get_rescuers_mut<'a>(&'a mut self) -> &'a mut framework::Rescuers;
+
+    fn get_arounds<'a>(&'a self) -> &'a framework::Arounds;
+    fn get_arounds_mut<'a>(&'a mut self) -> &'a mut framework::Arounds;
+
+    fn get_helpers<'a>(&'a self) -> &'a framework::Helpers;
+    fn get_helpers_mut<'a>(&'a mut self) -> &'a mut framework::Helpers;
+
+    fn get_security<'a>(&'a self) -> &'a framework::Security;
+    fn get_security_mut<'a>(&'a mut self) -> &'a mut framework::Security;
+
+    /// Builds the responses of the errors raised within this subtree
+    fn get_error_formatters(&self) -> Option<&framework::ErrorFormatters>;
+
+    /// The part of the route template this node matches, if any
+    fn get_path_template(&self) -> Option<String>;
+
+    /// The API version this node serves, if any
+    fn get_version(&self) -> Option<&str>;
+
     fn push_node<'a>(&'a self, _info: &mut framework::CallInfo<'a>);
 }
 
@@ -45,6 +74,29 @@ macro_rules! impl_nesting {
             fn get_after<'a>(&'a self) -> &'a ::framework::Callbacks { &self.after }
             fn get_after_mut<'a>(&'a mut self) -> &'a mut ::framework::Callbacks { &mut self.after }
 
+            fn get_ensure<'a>(&'a self) -> &'a ::framework::EnsureCallbacks { &self.ensure }
+            fn get_ensure_mut<'a>(&'a mut self) -> &'a mut ::framework::EnsureCallbacks { &mut self.ensure }
+
+            fn get_response_hooks<'a>(&'a self) -> &'a ::framework::ResponseHooks { &self.response_hooks }
+            fn get_response_hooks_mut<'a>(&'a mut self) -> &'a mut ::framework::ResponseHooks { &mut self.response_hooks }
+
+            fn get_rescuers<'a>(&'a self) -> &'a ::framework::Rescuers { &self.rescuers }
+            fn get_rescuers_mut<'a>(&'a mut self) -> &'a mut ::framework::Rescuers { &mut self.rescuers }
+
+            fn get_arounds<'a>(&'a self) -> &'a ::framework::Arounds { &self.arounds }
+            fn get_arounds_mut<'a>(&'a mut self) -> &'a mut ::framework::Arounds { &mut self.arounds }
+
+            fn get_helpers<'a>(&'a self) -> &'a ::framework::Helpers { &self.helpers }
+            fn get_helpers_mut<'a>(&'a mut self) -> &'a mut ::framework::Helpers { &mut self.helpers }
+
+            fn get_security<'a>(&'a self) -> &'a ::framework::Security { &self.security }
+            fn get_security_mut<'a>(&'a mut self) -> &'a mut ::framework::Security { &mut self.security }
+
+            fn get_error_formatters(&self) -> Option<&::framework::ErrorFormatters> { self.error_formatters() }
+
+            fn get_path_template(&self) -> Option<String> { self.path_template() }
+            fn get_version(&self) -> Option<&str> { self.api_version() }
+
             fn push_node<'a>(&'a self, _info: &mut ::framework::CallInfo<'a>) {
                 _info.parents.push(self);
             }
@@ -130,9 +182,90 @@ pub trait Nesting: Node {
         self.get_after_validation_mut().push(Box::new(callback));
     }
 
+    /*
+     * Callbacks restricted to some endpoints of the subtree
+     */
+
+    fn before_with<F: 'static>(&mut self, filter: framework::CallbackFilter, callback: F) where F: for<'a> Fn(&'a mut client::Client, &JsonValue)
+    -> backend::HandleSuccessResult + Send+Sync {
+        self.get_before_mut().push(filter.wrap(callback));
+    }
+    fn before_validation_with<F: 'static>(&mut self, filter: framework::CallbackFilter, callback: F) where F: for<'a> Fn(&'a mut client::Client, &JsonValue)
+    -> backend::HandleSuccessResult + Send+Sync {
+        self.get_before_validation_mut().push(filter.wrap(callback));
+    }
+    fn after_with<F: 'static>(&mut self, filter: framework::CallbackFilter, callback: F) where F: for<'a> Fn(&'a mut client::Client, &JsonValue)
+    -> backend::HandleSuccessResult + Send+Sync {
+        self.get_after_mut().push(filter.wrap(callback));
+    }
+    fn after_validation_with<F: 'static>(&mut self, filter: framework::CallbackFilter, callback: F) where F: for<'a> Fn(&'a mut client::Client, &JsonValue)
+    -> backend::HandleSuccessResult + Send+Sync {
+        self.get_after_validation_mut().push(filter.wrap(callback));
+    }
+
+    /// Runs after the endpoint on success and on every error, innermost first
+    fn ensure<F: 'static>(&mut self, callback: F) where F: for<'a, 'r> Fn(&'a mut client::Client, &JsonValue, framework::Outcome<'r>)
+    + Send+Sync {
+        self.get_ensure_mut().push(Box::new(callback));
+    }
+
+    /// Adjusts the final response of every request routed to this subtree,
+    /// including the error responses and the `404 Not Found` of its paths
+    /// without endpoints. Hooks of inner nodes run first.
+    fn on_response<F: 'static>(&mut self, hook: F) where F: Fn(&backend::Request, &mut backend::Response) + Send+Sync {
+        self.get_response_hooks_mut().push(Box::new(hook));
+    }
+
+    /// Wraps execution of every endpoint of this subtree. Middleware of outer
+    /// nodes wraps the middleware of inner ones.
+    fn around<F: 'static>(&mut self, middleware: F)
+    where F: for<'a, 'n> Fn(client::Client<'a>, &mut JsonValue, framework::Next<'n>) -> client::ClientResult<'a> + Send+Sync {
+        self.around_middleware(middleware);
+    }
+    fn around_middleware<M: framework::Around + 'static>(&mut self, middleware: M) {
+        self.get_arounds_mut().push(Box::new(middleware));
+    }
+
+    /// Handles errors of type `E` raised by endpoints of this subtree. Handlers
+    /// of inner nodes take precedence over the outer ones.
+    fn rescue_from<E: errors::Error, F: 'static>(&mut self, handler: F)
+    where F: for<'a> Fn(&E, client::Client<'a>) -> client::ClientResult<'a> + Send+Sync {
+        self.get_rescuers_mut().push(framework::Rescuer::new(handler));
+    }
+
+    /// Defines a helper callable with `client.helper(name, args)` from the
+    /// endpoints of this subtree. Inner nodes may redefine it.
+    fn helper<F: 'static>(&mut self, name: &str, helper: F)
+    where F: for<'a> Fn(&mut client::Client<'a>, &JsonValue) -> backend::HandleResult<JsonValue> + Send+Sync {
+        self.get_helpers_mut().insert_named(name, Box::new(helper));
+    }
+
+    /// Shares a helper object, usually a boxed trait object, available with
+    /// `client.helpers::<T>()` from the endpoints of this subtree.
+    fn helpers<T: Any + Send + Sync>(&mut self, helpers: T) {
+        self.get_helpers_mut().insert(helpers);
+    }
+
+    /// Documents that endpoints of this subtree are protected by `scheme`
+    fn security_scheme(&mut self, scheme: framework::SecurityScheme) {
+        self.get_security_mut().schemes.push(scheme);
+    }
+
+    /// Requires the principal to be granted every one of `scopes` by all
+    /// endpoints of this subtree
+    fn requires_scopes(&mut self, scopes: &[&str]) {
+        self.get_security_mut().scopes.extend(framework::security::to_strings(scopes));
+    }
+
+    /// Requires the principal to have one of `roles` for all endpoints of this subtree
+    fn requires_roles(&mut self, roles: &[&str]) {
+        self.get_security_mut().roles.extend(framework::security::to_strings(roles));
+    }
+
     fn call_handlers<'a, 'r>(&'a self, rest_path: &str, params: &mut JsonValue, req: &'r mut (backend::Request + 'r),
                          info: &mut framework::CallInfo<'a>) -> backend::HandleResult<backend::Response> {
 
+        let parents_len = info.parents.len();
         for handler in self.get_handlers().iter() {
             match handler.api_call(rest_path, params, req, info) {
                 Ok(response) => return Ok(response),
@@ -142,6 +275,11 @@ pub trait Nesting: Node {
                     }
                 }
             };
+            // Nodes of the branch that didn't match must not affect its siblings
+            if info.parents.len() > info.deepest_parents.len() {
+                info.deepest_parents = info.parents.clone();
+            }
+            info.parents.truncate(parents_len);
         }
 
         Err(error_response!(errors::NotMatch))
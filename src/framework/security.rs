@@ -0,0 +1,98 @@
+use typemap;
+
+use errors;
+use json::{JsonValue};
+
+/// The authenticated party of a request, stored in the client extensions by
+/// the authentication middleware
+#[derive(Clone, Debug)]
+pub struct Principal {
+    pub id: String,
+    pub scopes: Vec<String>,
+    pub roles: Vec<String>
+}
+
+impl Principal {
+    pub fn new(id: &str) -> Principal {
+        Principal {
+            id: id.to_string(),
+            scopes: vec![],
+            roles: vec![]
+        }
+    }
+}
+
+pub struct PrincipalKey;
+
+impl typemap::Key for PrincipalKey {
+    type Value = Principal;
+}
+
+/// A way of authenticating requests, shown in the Swagger `securityDefinitions`
+#[derive(Clone)]
+pub struct SecurityScheme {
+    pub name: String,
+    /// The Swagger 2.0 Security Scheme Object
+    pub definition: JsonValue
+}
+
+impl SecurityScheme {
+    pub fn new(name: &str, definition: JsonValue) -> SecurityScheme {
+        SecurityScheme {
+            name: name.to_string(),
+            definition: definition
+        }
+    }
+}
+
+/// Security requirements of a node or an endpoint
+pub struct Security {
+    pub schemes: Vec<SecurityScheme>,
+    /// Every one of these scopes must be granted to the principal
+    pub scopes: Vec<String>,
+    /// At least one of these roles must be granted to the principal
+    pub roles: Vec<String>
+}
+
+impl Security {
+    pub fn new() -> Security {
+        Security {
+            schemes: vec![],
+            scopes: vec![],
+            roles: vec![]
+        }
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        !self.scopes.is_empty() || !self.roles.is_empty()
+    }
+
+    pub fn authorize(&self, principal: Option<&Principal>) -> Result<(), errors::Forbidden> {
+        if !self.is_restricted() {
+            return Ok(());
+        }
+
+        let principal = match principal {
+            Some(principal) => principal,
+            None => return Err(errors::Forbidden { reason: "Authentication is required".to_string() })
+        };
+
+        let missing: Vec<&str> = self.scopes.iter()
+            .filter(|scope| !principal.scopes.contains(scope))
+            .map(|scope| &scope[..])
+            .collect();
+        if !missing.is_empty() {
+            return Err(errors::Forbidden { reason: format!("Missing scopes: {}", missing.join(", ")) });
+        }
+
+        if !self.roles.is_empty() && !self.roles.iter().any(|role| principal.roles.contains(role)) {
+            return Err(errors::Forbidden { reason: format!("One of the roles is required: {}", self.roles.join(", ")) });
+        }
+
+        Ok(())
+    }
+}
+
+pub fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
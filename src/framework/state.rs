@@ -0,0 +1,127 @@
+use std::any::{self, Any, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use typemap;
+
+use backend;
+use errors;
+use framework::client::Client;
+use framework::Outcome;
+
+/// Type map key of values stored by their own type
+pub struct Entry<T>(PhantomData<T>);
+
+impl<T: Any> typemap::Key for Entry<T> {
+    type Value = T;
+}
+
+/// Types of the request-scoped values built so far, in construction order
+struct Built;
+
+impl typemap::Key for Built {
+    type Value = Vec<TypeId>;
+}
+
+pub fn missing<T: Any>() -> errors::MissingDependency {
+    errors::MissingDependency {
+        type_name: any::type_name::<T>().to_string()
+    }
+}
+
+/// Values shared by every request, keyed by their type.
+pub struct State {
+    map: typemap::ShareMap
+}
+
+impl State {
+    pub fn new() -> State {
+        State {
+            map: typemap::ShareMap::custom()
+        }
+    }
+
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.map.insert::<Entry<T>>(value)
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.map.get::<Entry<T>>()
+    }
+
+    pub fn contains<T: Any + Send + Sync>(&self) -> bool {
+        self.map.contains::<Entry<T>>()
+    }
+}
+
+pub type ProvideFn = Box<for<'a> Fn(&mut Client<'a>) -> backend::HandleSuccessResult + 'static + Sync + Send>;
+pub type CleanupFn = Box<for<'a, 'r> Fn(&mut (backend::Request + 'a), Outcome<'r>) + 'static + Sync + Send>;
+
+struct Provider {
+    provide: ProvideFn,
+    cleanup: CleanupFn
+}
+
+/// Factories of request-scoped values.
+///
+/// A value is built the first time it is requested while handling a request,
+/// stored in the request extensions and cleaned up once the request is over.
+pub struct Providers {
+    map: HashMap<TypeId, Provider>
+}
+
+impl Providers {
+    pub fn new() -> Providers {
+        Providers {
+            map: HashMap::new()
+        }
+    }
+
+    pub fn register<T: Any, F: 'static, C: 'static>(&mut self, factory: F, cleanup: C)
+    where F: for<'a> Fn(&mut Client<'a>) -> backend::HandleResult<T> + Send+Sync,
+          C: for<'r> Fn(T, Outcome<'r>) + Send+Sync {
+        self.map.insert(TypeId::of::<T>(), Provider {
+            provide: Box::new(move |client| {
+                let value = try!(factory(client));
+                let ext = client.request.ext_mut();
+                ext.insert::<Entry<T>>(value);
+                ext.entry::<Built>().or_insert_with(Vec::new).push(TypeId::of::<T>());
+                Ok(())
+            }),
+            cleanup: Box::new(move |req, outcome| {
+                if let Some(value) = req.ext_mut().remove::<Entry<T>>() {
+                    cleanup(value, outcome);
+                }
+            })
+        });
+    }
+
+    pub fn contains<T: Any>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns the value of type `T` for the current request, building it if needed
+    pub fn resolve<'c, 'a, T: Any>(&self, client: &'c mut Client<'a>) -> backend::HandleResult<&'c mut T> {
+        if !client.request.ext().contains::<Entry<T>>() {
+            match self.map.get(&TypeId::of::<T>()) {
+                Some(provider) => try!((provider.provide)(client)),
+                None => return Err(error_response!(missing::<T>()))
+            }
+        }
+
+        Ok(client.request.ext_mut().get_mut::<Entry<T>>().unwrap())
+    }
+
+    /// Cleans up the values built while handling the request, the latest first
+    pub fn cleanup<'a, 'r>(&self, req: &mut (backend::Request + 'a), outcome: Outcome<'r>) {
+        let built = match req.ext_mut().remove::<Built>() {
+            Some(built) => built,
+            None => return
+        };
+
+        for type_id in built.iter().rev() {
+            if let Some(provider) = self.map.get(type_id) {
+                (provider.cleanup)(req, outcome);
+            }
+        }
+    }
+}
@@ -19,6 +19,11 @@ extern crate queryst;
 extern crate jsonway;
 extern crate typeable;
 extern crate traitobject;
+extern crate rustc_serialize;
+extern crate ring;
+extern crate flate2;
+#[cfg(feature = "brotli")]
+extern crate brotli;
 
 #[macro_use]
 extern crate lazy_static;
@@ -30,7 +35,8 @@ pub use json::{JsonValue};
 pub use backend::{Request, SimpleRequest, Response, Handler, HandleResult, HandleSuccessResult, ResponseBody};
 pub use errors::{ErrorResponse};
 pub use framework::{
-    Endpoint, Client, Api, Application, Namespace, Nesting, Media, Versioning
+    Endpoint, Client, Api, Application, Namespace, Nesting, Media, Versioning,
+    CallbackFilter, EndpointMatcher
 };
 
 pub mod prelude {
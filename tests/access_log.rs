@@ -0,0 +1,79 @@
+use std::sync::{Arc, Mutex};
+use serde_json::{self, Value};
+
+use rustless::batteries::access_log::{self, AccessLog, Format};
+use rustless::batteries::request_id::{self, RequestId};
+use rustless::{Nesting, Versioning, JsonValue};
+
+#[test]
+fn it_logs_requests_in_common_log_format() {
+    let lines = Arc::new(Mutex::new(vec![]));
+    let log = lines.clone();
+
+    let mut app = app!(|api| {
+        api.prefix("api");
+        api.version("v1", Versioning::Path);
+
+        api.namespace("users/:user_id", |user_ns| {
+            user_ns.get("orders", |endpoint| {
+                endpoint.handle(|client, _params| {
+                    client.json(&JsonValue::String("orders".to_string()))
+                })
+            });
+        });
+    });
+    request_id::enable(&mut app, RequestId::new());
+    access_log::enable(&mut app, AccessLog::build(move |access_log| {
+        access_log.format(Format::Common);
+        access_log.log_headers(&["User-Agent", "Authorization"]);
+        access_log.writer(move |line| log.lock().unwrap().push(line.to_string()));
+    }));
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/v1/users/7/orders?token=secret&page=2", |rq| {
+        rq.headers_mut().set_raw("X-Request-Id", vec![b"req-1".to_vec()]);
+        rq.headers_mut().set_raw("User-Agent", vec![b"curl".to_vec()]);
+        rq.headers_mut().set_raw("Authorization", vec![b"Bearer abc".to_vec()]);
+    }).ok().unwrap();
+    // The line is written along with the response
+    assert!(lines.lock().unwrap().is_empty());
+    assert_eq!(resp_body!(response), "\"orders\"");
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 1);
+    let line = &lines[0];
+    assert!(line.starts_with("127.0.0.1 - - ["));
+    assert!(line.contains("] \"GET /api/v1/users/7/orders?token=[REDACTED]&page=2\" 200 8 \"/api/v1/users/:user_id/orders\" \"v1\" "));
+    assert!(line.ends_with(" \"req-1\" \"User-Agent: curl\" \"Authorization: [REDACTED]\""));
+}
+
+#[test]
+fn it_logs_requests_as_json_lines() {
+    let lines = Arc::new(Mutex::new(vec![]));
+    let log = lines.clone();
+
+    let mut app = app!(|api| {
+        api.prefix("api");
+        api.version("v1", Versioning::Path);
+    });
+    request_id::enable(&mut app, RequestId::new());
+    access_log::enable(&mut app, AccessLog::build(move |access_log| {
+        access_log.format(Format::JsonLines);
+        access_log.writer(move |line| log.lock().unwrap().push(line.to_string()));
+    }));
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/v1/unknown", |rq| {
+        rq.set_remote_str("10.0.0.2:4000");
+    }).err().unwrap();
+
+    let lines = lines.lock().unwrap();
+    let entry: Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(entry.find("method").unwrap().as_str().unwrap(), "GET");
+    assert_eq!(entry.find("path").unwrap().as_str().unwrap(), "/api/v1/unknown");
+    assert!(entry.find("route").unwrap().is_null());
+    assert_eq!(entry.find("status").unwrap().as_u64().unwrap(), 404);
+    assert_eq!(entry.find("remote_addr").unwrap().as_str().unwrap(), "10.0.0.2");
+    assert_eq!(entry.find("request_id").unwrap().as_str().unwrap().len(), 36);
+    assert!(entry.find("latency_ms").unwrap().as_f64().is_some());
+    assert!(entry.find("size").unwrap().is_null());
+    assert!(entry.find("headers").is_none());
+}
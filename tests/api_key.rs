@@ -0,0 +1,89 @@
+use rustless::server::status;
+use rustless::batteries::auth::AuthExt;
+use rustless::batteries::auth::api_key::{self, ApiKey, KeyLocation, KeyInfo, MemoryStore, FileStore};
+use rustless::batteries::swagger;
+use rustless::{Nesting};
+
+#[test]
+fn it_authenticates_keys_from_headers() {
+    let mut store = MemoryStore::new();
+    store.insert("k-acme", KeyInfo::new("acme", &["orders:write"]));
+    let app = app!(move |api| {
+        api.prefix("api");
+
+        api.namespace("partners", move |partners_ns| {
+            api_key::protect(partners_ns, ApiKey::new(KeyLocation::Header("X-Api-Key".to_string()), store));
+
+            partners_ns.get("me", |endpoint| {
+                endpoint.handle(|client, _params| {
+                    let owner = client.principal().unwrap().id.clone();
+                    client.text(owner)
+                })
+            });
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/partners/me", |rq| {
+        rq.headers_mut().set_raw("X-Api-Key", vec![b"k-acme".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_body!(response), "acme");
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/partners/me").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Unauthorized);
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/partners/me", |rq| {
+        rq.headers_mut().set_raw("X-Api-Key", vec![b"k-unknown".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Unauthorized);
+}
+
+#[test]
+fn it_authenticates_keys_from_query_with_a_file_store() {
+    let store = FileStore::open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/api_keys.txt")).unwrap();
+    let app = app!(move |api| {
+        api.prefix("api");
+
+        api.namespace("partners", move |partners_ns| {
+            api_key::protect(partners_ns, ApiKey::new(KeyLocation::Query("api_key".to_string()), store));
+
+            partners_ns.get("me", |endpoint| {
+                endpoint.handle(|client, _params| {
+                    let owner = client.principal().unwrap().id.clone();
+                    client.text(owner)
+                })
+            });
+
+            partners_ns.post("orders", |endpoint| {
+                endpoint.requires_scopes(&["orders:write"]);
+                endpoint.handle(|client, _params| client.empty())
+            });
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/partners/me?api_key=k-globex").ok().unwrap();
+    assert_eq!(resp_body!(response), "globex");
+
+    let response = call_app!(app, Post, "http://127.0.0.1:3000/api/partners/orders?api_key=k-acme").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/partners/orders?api_key=k-globex").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Forbidden);
+}
+
+#[test]
+fn it_documents_api_keys_in_swagger() {
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("partners", |partners_ns| {
+            api_key::protect(partners_ns, ApiKey::new(KeyLocation::Header("X-Api-Key".to_string()), MemoryStore::new()));
+            edp_stub!(partners_ns);
+        });
+    });
+
+    let spec = swagger::build_spec(&app, swagger::Spec::default());
+    let definition = spec.find_path(&["securityDefinitions", "api_key"]).unwrap();
+    assert_eq!(definition.find("type").unwrap().as_str().unwrap(), "apiKey");
+    assert_eq!(definition.find("name").unwrap().as_str().unwrap(), "X-Api-Key");
+    assert_eq!(definition.find("in").unwrap().as_str().unwrap(), "header");
+}
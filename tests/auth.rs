@@ -0,0 +1,178 @@
+use rustless::server::{header, status};
+use rustless::errors;
+use rustless::batteries::auth::{self, AuthExt};
+use rustless::batteries::auth::basic;
+use rustless::batteries::swagger;
+use rustless::framework::SecurityScheme;
+use rustless::{Nesting};
+use jsonway;
+
+fn basic_credentials(username: &str, password: &str) -> header::Authorization<header::Basic> {
+    header::Authorization(header::Basic {
+        username: username.to_string(),
+        password: Some(password.to_string())
+    })
+}
+
+#[test]
+fn it_authenticates_with_basic_credentials() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("admin", |admin_ns| {
+            basic::protect(admin_ns, basic::Basic::new("admin area", |_client, username, password| {
+                if username == "root" && password == "secret" {
+                    Some(auth::Principal::new(username))
+                } else {
+                    None
+                }
+            }));
+
+            admin_ns.get("me", |endpoint| {
+                endpoint.handle(|client, _params| {
+                    let id = client.principal().unwrap().id.clone();
+                    client.text(id)
+                })
+            });
+        });
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/admin/me").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Unauthorized);
+    assert_eq!(err_resp.response.headers.get_raw("WWW-Authenticate").unwrap()[0], b"Basic realm=\"admin area\"".to_vec());
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/admin/me", |rq| {
+        rq.headers_mut().set(basic_credentials("root", "wrong"));
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Unauthorized);
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/admin/me", |rq| {
+        rq.headers_mut().set(basic_credentials("root", "secret"));
+    }).ok().unwrap();
+    assert_eq!(resp_body!(response), "root");
+}
+
+#[test]
+fn it_documents_security_schemes_in_swagger() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.get("status", |endpoint| {
+            endpoint.handle(|client, _params| client.empty())
+        });
+
+        api.namespace("admin", |admin_ns| {
+            basic::protect(admin_ns, basic::Basic::new("admin area", |_client, _username, _password| None));
+
+            admin_ns.get("me", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+        });
+    });
+
+    let spec = swagger::build_spec(&app, swagger::Spec::default());
+    assert_eq!(spec.find_path(&["securityDefinitions", "basic", "type"]).unwrap().as_str().unwrap(), "basic");
+
+    let security = spec.find_path(&["paths", "/admin/me", "get", "security"]).unwrap().as_array().unwrap();
+    assert!(security[0].find("basic").is_some());
+    assert!(spec.find_path(&["paths", "/status", "get", "security"]).is_none());
+}
+
+fn principal_for(username: &str) -> auth::Principal {
+    let mut principal = auth::Principal::new(username);
+    match username {
+        "clerk" => principal.scopes = vec!["orders:read".to_string()],
+        "manager" => {
+            principal.scopes = vec!["orders:read".to_string(), "orders:write".to_string()];
+            principal.roles = vec!["manager".to_string()];
+        },
+        _ => ()
+    }
+    principal
+}
+
+#[test]
+fn it_requires_scopes_and_roles() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("orders", |orders_ns| {
+            basic::protect(orders_ns, basic::Basic::new("orders", |_client, username, _password| {
+                Some(principal_for(username))
+            }));
+            orders_ns.security_scheme(SecurityScheme::new("oauth", jsonway::object(|scheme| {
+                scheme.set("type", "oauth2".to_string());
+                scheme.set("flow", "application".to_string());
+                scheme.set("tokenUrl", "https://example.com/token".to_string());
+            }).unwrap()));
+            orders_ns.requires_scopes(&["orders:read"]);
+
+            orders_ns.get("", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+
+            orders_ns.post("", |endpoint| {
+                endpoint.requires_scopes(&["orders:write"]);
+                endpoint.params(|params| {
+                    params.req_typed("amount", ::valico::json_dsl::u64())
+                });
+                endpoint.handle(|client, _params| client.empty())
+            });
+
+            orders_ns.namespace("reports", |reports_ns| {
+                reports_ns.requires_roles(&["manager", "auditor"]);
+
+                reports_ns.get("", |endpoint| {
+                    endpoint.handle(|client, _params| client.empty())
+                });
+            });
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/orders", |rq| {
+        rq.headers_mut().set(basic_credentials("clerk", ""));
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/orders", |rq| {
+        rq.headers_mut().set(basic_credentials("guest", ""));
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Forbidden);
+
+    // Authorization is checked before the parameters are validated
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/orders", |rq| {
+        rq.headers_mut().set(basic_credentials("clerk", ""));
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Forbidden);
+    assert_eq!(err_resp.error.downcast::<errors::Forbidden>().unwrap().reason, "Missing scopes: orders:write");
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/orders", |rq| {
+        rq.headers_mut().set(basic_credentials("manager", ""));
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::BadRequest);
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/orders/reports", |rq| {
+        rq.headers_mut().set(basic_credentials("clerk", ""));
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Forbidden);
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/orders/reports", |rq| {
+        rq.headers_mut().set(basic_credentials("manager", ""));
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+
+    let spec = swagger::build_spec(&app, swagger::Spec::default());
+    let security = spec.find_path(&["paths", "/orders", "post", "security"]).unwrap().as_array().unwrap();
+    assert_eq!(security.len(), 1);
+    assert!(security[0].find("basic").unwrap().as_array().unwrap().is_empty());
+    let scopes = security[0].find("oauth").unwrap().as_array().unwrap();
+    assert_eq!(scopes.iter().map(|s| s.as_str().unwrap()).collect::<Vec<&str>>(), vec!["orders:read", "orders:write"]);
+
+    let roles = spec.find_path(&["paths", "/orders/reports", "get", "x-roles"]).unwrap().as_array().unwrap();
+    assert_eq!(roles.len(), 1);
+    assert_eq!(roles[0].as_array().unwrap().iter().map(|r| r.as_str().unwrap()).collect::<Vec<&str>>(), vec!["manager", "auditor"]);
+    assert!(spec.find_path(&["paths", "/orders", "post", "x-roles"]).is_none());
+}
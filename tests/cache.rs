@@ -0,0 +1,206 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use rustless::server::header;
+use rustless::batteries::auth::{self, basic, AuthExt};
+use rustless::batteries::cache::{self, CacheExt, ResponseCache, ResponseCacheKey};
+use rustless::{Nesting, Versioning};
+
+#[test]
+fn it_sets_cache_control_directives() {
+    let mut app = app!(|api| {
+        api.prefix("api");
+
+        api.get("articles/:id", |endpoint| {
+            endpoint.cache(|cache| {
+                cache.public();
+                cache.max_age(60);
+                cache.s_maxage(300);
+                cache.vary(&["Accept-Language"]);
+                cache.store_for(Duration::from_secs(60));
+            });
+            endpoint.handle(|client, _params| client.text("article".to_string()))
+        });
+
+        api.get("me", |endpoint| {
+            endpoint.cache(|cache| {
+                cache.private();
+                cache.max_age(10);
+                cache.store_for(Duration::from_secs(60));
+            });
+            endpoint.handle(|client, _params| client.text("me".to_string()))
+        });
+
+        api.get("live", |endpoint| {
+            endpoint.cache(|cache| cache.no_store());
+            endpoint.handle(|client, _params| client.text("live".to_string()))
+        });
+    });
+    cache::enable(&mut app, ResponseCache::new());
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/articles/1").ok().unwrap();
+    assert_eq!(resp_header!(response, "Cache-Control").unwrap(), "public, max-age=60, s-maxage=300");
+    assert_eq!(resp_header!(response, "Vary").unwrap(), "Accept-Language");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/me").ok().unwrap();
+    assert_eq!(resp_header!(response, "Cache-Control").unwrap(), "private, max-age=10");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/live").ok().unwrap();
+    assert_eq!(resp_header!(response, "Cache-Control").unwrap(), "no-store");
+
+    // Private responses are never kept in the shared in-process cache
+    assert_eq!(app.ext.get::<ResponseCacheKey>().unwrap().len(), 1);
+}
+
+#[test]
+fn it_serves_stored_responses_by_path_query_and_vary_headers() {
+    let renders = Arc::new(AtomicUsize::new(0));
+    let counter = renders.clone();
+
+    let mut app = app!(move |api| {
+        api.prefix("api");
+
+        api.get("articles/:id", move |endpoint| {
+            endpoint.cache(|cache| {
+                cache.public();
+                cache.max_age(60);
+                cache.s_maxage(300);
+                cache.vary(&["Accept-Language"]);
+                cache.store_for(Duration::from_secs(60));
+            });
+            endpoint.handle(move |client, params| {
+                let render = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                client.text(format!("article {} #{}", params.find("id").unwrap().as_str().unwrap(), render))
+            })
+        });
+    });
+    cache::enable(&mut app, ResponseCache::new());
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/articles/1").ok().unwrap();
+    assert!(resp_header!(response, "Age").is_none());
+    assert_eq!(resp_body!(response), "article 1 #1");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/articles/1").ok().unwrap();
+    assert_eq!(resp_header!(response, "Age").unwrap(), "0");
+    assert_eq!(resp_header!(response, "Cache-Control").unwrap(), "public, max-age=60, s-maxage=300");
+    assert_eq!(resp_body!(response), "article 1 #1");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/articles/1?full=true").ok().unwrap();
+    assert_eq!(resp_body!(response), "article 1 #2");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/articles/1", |rq| {
+        rq.headers_mut().set_raw("Accept-Language", vec![b"fr".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_body!(response), "article 1 #3");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/articles/2").ok().unwrap();
+    assert_eq!(resp_body!(response), "article 2 #4");
+    assert_eq!(renders.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn it_invalidates_stored_responses_from_other_handlers() {
+    let renders = Arc::new(AtomicUsize::new(0));
+
+    let mut app = app!(move |api| {
+        api.prefix("api");
+
+        api.get("articles/:id", move |endpoint| {
+            endpoint.cache(|cache| {
+                cache.public();
+                cache.store_for(Duration::from_secs(60));
+            });
+            endpoint.handle(move |client, params| {
+                let render = renders.fetch_add(1, Ordering::SeqCst) + 1;
+                client.text(format!("article {} #{}", params.find("id").unwrap().as_str().unwrap(), render))
+            })
+        });
+
+        api.put("articles/:id", |endpoint| {
+            endpoint.handle(|client, params| {
+                let path = format!("/api/articles/{}", params.find("id").unwrap().as_str().unwrap());
+                client.app.ext.get::<ResponseCacheKey>().unwrap().invalidate(&path);
+                client.empty()
+            })
+        });
+    });
+    cache::enable(&mut app, ResponseCache::new());
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/articles/1").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/articles/1?full=true").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/articles/2").ok().unwrap();
+
+    call_app!(app, Put, "http://127.0.0.1:3000/api/articles/1").ok().unwrap();
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/articles/1").ok().unwrap();
+    assert_eq!(resp_body!(response), "article 1 #4");
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/articles/2").ok().unwrap();
+    assert_eq!(resp_body!(response), "article 2 #3");
+
+    let store = app.ext.get::<ResponseCacheKey>().unwrap();
+    assert_eq!(store.invalidate_prefix("/api/articles/"), 2);
+    assert!(store.is_empty());
+    assert_eq!(store.size(), 0);
+}
+
+#[test]
+fn it_does_not_share_responses_of_authenticated_requests() {
+    let mut app = app!(|api| {
+        api.prefix("api");
+        basic::protect(api, basic::Basic::new("api", |_client, username, _password| {
+            Some(auth::Principal::new(username))
+        }));
+
+        api.get("me", |endpoint| {
+            endpoint.cache(|cache| {
+                cache.max_age(60);
+                cache.store_for(Duration::from_secs(60));
+            });
+            endpoint.handle(|client, _params| {
+                let id = client.principal().unwrap().id.clone();
+                client.text(id)
+            })
+        });
+    });
+    cache::enable(&mut app, ResponseCache::new());
+
+    for username in &["alice", "bob", "alice"] {
+        let response = call_app!(app, Get, "http://127.0.0.1:3000/api/me", |rq| {
+            rq.headers_mut().set(header::Authorization(header::Basic {
+                username: username.to_string(),
+                password: Some("secret".to_string())
+            }));
+        }).ok().unwrap();
+        assert!(resp_header!(response, "Age").is_none());
+        assert_eq!(resp_body!(response), *username);
+    }
+
+    assert!(app.ext.get::<ResponseCacheKey>().unwrap().is_empty());
+}
+
+#[test]
+fn it_keeps_responses_of_header_versioned_apis_apart() {
+    let mut app = app!(|api| {
+        for version in &["v1", "v2"] {
+            api.mount(::rustless::Api::build(|versioned_api| {
+                versioned_api.version(version, Versioning::AcceptHeader("shop"));
+                versioned_api.get("articles", |endpoint| {
+                    endpoint.cache(|cache| {
+                        cache.public();
+                        cache.store_for(Duration::from_secs(60));
+                    });
+                    endpoint.handle(move |client, _params| client.text(format!("articles {}", version)))
+                });
+            }));
+        }
+    });
+    cache::enable(&mut app, ResponseCache::new());
+
+    for version in &["v1", "v2", "v1"] {
+        let response = call_app!(app, Get, "http://127.0.0.1:3000/articles", |rq| {
+            rq.headers_mut().set_raw("Accept", vec![format!("application/vnd.shop.{}+json", version).into_bytes()]);
+        }).ok().unwrap();
+        assert_eq!(resp_body!(response), format!("articles {}", version));
+    }
+}
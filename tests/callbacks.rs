@@ -1,10 +1,12 @@
 use std::error;
+use std::sync::{Arc, Mutex};
 use std::error::Error as StdError;
 use std::fmt;
 use valico::json_dsl;
-use rustless::{self, Nesting};
+use rustless::{self, Nesting, CallbackFilter, EndpointMatcher};
 use rustless::server::status;
 use rustless::errors::{Error};
+use rustless::batteries::auth::{self, AuthExt};
 
 #[derive(Debug)]
 pub struct UnauthorizedError;
@@ -76,3 +78,210 @@ fn it_invokes_callbacks() {
     assert_eq!(response.status, status::StatusCode::Ok);
 
 }
+
+#[test]
+fn it_invokes_ensure_callbacks_on_every_outcome() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let api_log = log.clone();
+    let ns_log = log.clone();
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.ensure(move |_client, _params, outcome| {
+            api_log.lock().unwrap().push(match outcome {
+                Ok(response) => format!("api: {}", response.status),
+                Err(err_resp) => format!("api: {}", err_resp.error)
+            });
+        });
+
+        api.namespace("admin", |admin_ns| {
+            admin_ns.ensure(move |_client, _params, outcome| {
+                ns_log.lock().unwrap().push(format!("namespace: {}", outcome.is_ok()));
+            });
+
+            admin_ns.get("server_status", |endpoint| {
+                endpoint.params(|params| {
+                    params.req_typed("token", json_dsl::string())
+                });
+
+                endpoint.handle(|client, params| {
+                    if params.find("token").unwrap().as_str().unwrap() == "password1" {
+                        client.text("Everything is OK".to_string())
+                    } else {
+                        client.error(UnauthorizedError)
+                    }
+                })
+            });
+        })
+    });
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/admin/server_status?token=password1").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/admin/server_status?token=wrong").err().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/admin/server_status").err().unwrap();
+
+    assert_eq!(*log.lock().unwrap(), vec![
+        "namespace: true", "api: 200 OK",
+        "namespace: false", "api: Unauthorized",
+        "namespace: false", "api: Validation"
+    ]);
+}
+
+#[test]
+fn it_invokes_ensure_callbacks_on_request_errors() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let api_log = log.clone();
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.ensure(move |_client, _params, outcome| {
+            api_log.lock().unwrap().push(match outcome {
+                Ok(response) => response.status,
+                Err(err_resp) => err_resp.response.as_ref().unwrap().status
+            });
+        });
+
+        api.namespace("admin", |admin_ns| {
+            admin_ns.params(|params| {
+                params.req_typed("token", json_dsl::string())
+            });
+
+            admin_ns.post("users", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+        })
+    });
+
+    call_app!(app, Post, "http://127.0.0.1:3000/api/admin/users").err().unwrap();
+    call_app!(app, Post, "http://127.0.0.1:3000/api/admin/users", |rq| {
+        rq.headers_mut().set_raw("Content-Type", vec![b"application/json".to_vec()]);
+        rq.push_string("{ not a json".to_string());
+    }).err().unwrap();
+
+    assert_eq!(*log.lock().unwrap(), vec![status::StatusCode::BadRequest, status::StatusCode::BadRequest]);
+}
+
+#[test]
+fn it_keeps_client_extensions_for_ensure_callbacks() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let api_log = log.clone();
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.before(|client, _params| {
+            client.set_principal(auth::Principal::new("admin"));
+            Ok(())
+        });
+
+        api.ensure(move |client, _params, outcome| {
+            api_log.lock().unwrap().push(format!("{}: {}",
+                client.principal().map(|principal| &principal.id[..]).unwrap_or("anonymous"),
+                outcome.is_ok()
+            ));
+        });
+
+        api.get("ok", |endpoint| {
+            endpoint.handle(|client, _params| client.empty())
+        });
+
+        api.get("fail", |endpoint| {
+            endpoint.handle(|client, _params| client.error(UnauthorizedError))
+        });
+    });
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/ok").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/fail").err().unwrap();
+
+    assert_eq!(*log.lock().unwrap(), vec!["admin: true", "admin: false"]);
+}
+
+#[test]
+fn it_invokes_endpoint_callbacks_after_parent_ones() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let ns_log = log.clone();
+    let endpoint_log = log.clone();
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("admin", move |admin_ns| {
+            admin_ns.before(move |_client, _params| {
+                ns_log.lock().unwrap().push("namespace");
+                Ok(())
+            });
+
+            admin_ns.get("server_status", move |endpoint| {
+                endpoint.before(move |_client, _params| {
+                    endpoint_log.lock().unwrap().push("endpoint");
+                    Ok(())
+                });
+
+                endpoint.handle(|client, _params| {
+                    client.text("Everything is OK".to_string())
+                })
+            });
+        })
+    });
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/admin/server_status").ok().unwrap();
+    assert_eq!(*log.lock().unwrap(), vec!["namespace", "endpoint"]);
+}
+
+#[test]
+fn it_filters_callbacks_by_endpoint() {
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("admin", |admin_ns| {
+            admin_ns.before_with(CallbackFilter::except(vec![EndpointMatcher::name("health")]), |_client, _params| {
+                Err(rustless::ErrorResponse{
+                    error: Box::new(UnauthorizedError) as Box<Error + Send>,
+                    response: None
+                })
+            });
+
+            admin_ns.after_with(CallbackFilter::only(vec![EndpointMatcher::method(rustless::server::method::Method::Post)]), |client, _params| {
+                client.set_status(status::StatusCode::Created);
+                Ok(())
+            });
+
+            admin_ns.get("health", |endpoint| {
+                endpoint.name("health");
+                endpoint.handle(|client, _params| client.empty())
+            });
+
+            admin_ns.get("server_status", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+        });
+
+        api.namespace("public", |public_ns| {
+            public_ns.after_with(CallbackFilter::only(vec![EndpointMatcher::path("/items")]), |client, _params| {
+                client.set_status(status::StatusCode::Created);
+                Ok(())
+            });
+
+            public_ns.post("items", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+
+            public_ns.post("others", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+        })
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/admin/health").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/admin/server_status").err().unwrap();
+    assert!(err_resp.error.is::<UnauthorizedError>());
+
+    let response = call_app!(app, Post, "http://127.0.0.1:3000/api/public/items").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Created);
+
+    let response = call_app!(app, Post, "http://127.0.0.1:3000/api/public/others").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+}
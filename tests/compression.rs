@@ -0,0 +1,98 @@
+use std::io::Read;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use jsonway;
+
+use rustless::batteries::compression::{self, Compression};
+use rustless::{Nesting, Response, EndpointMatcher};
+
+fn body_bytes(response: Response) -> Vec<u8> {
+    let mut vec = Vec::new();
+    response.body.unwrap().write_body(&mut ::rustless::ResponseBody::new(&mut vec)).unwrap();
+    vec
+}
+
+#[test]
+fn it_compresses_with_the_accepted_encoding() {
+    let app = app!(|api| {
+        api.prefix("api");
+        compression::enable(api, Compression::build(|compression| compression.min_size(100)));
+
+        api.get("items", |endpoint| {
+            endpoint.handle(|client, _params| {
+                client.json(&jsonway::array(|items| {
+                    for i in 0..50 {
+                        items.push(format!("item number {}", i));
+                    }
+                }).unwrap())
+            })
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/items", |rq| {
+        rq.headers_mut().set_raw("Accept-Encoding", vec![b"deflate;q=0.5, gzip".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_header!(response, "Content-Encoding").unwrap(), "gzip");
+    assert_eq!(resp_header!(response, "Vary").unwrap(), "Accept-Encoding");
+    let mut body = String::new();
+    GzDecoder::new(&body_bytes(response)[..]).read_to_string(&mut body).unwrap();
+    assert!(body.starts_with("[\"item number 0\","));
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/items", |rq| {
+        rq.headers_mut().set_raw("Accept-Encoding", vec![b"deflate".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_header!(response, "Content-Encoding").unwrap(), "deflate");
+    let mut body = String::new();
+    ZlibDecoder::new(&body_bytes(response)[..]).read_to_string(&mut body).unwrap();
+    assert!(body.ends_with("\"item number 49\"]"));
+}
+
+#[test]
+fn it_skips_small_uncompressible_and_excluded_responses() {
+    let app = app!(|api| {
+        api.prefix("api");
+
+        compression::enable(api, Compression::build(|compression| {
+            compression.min_size(100);
+            compression.except(vec![EndpointMatcher::name("archive")]);
+        }));
+
+        api.get("items", |endpoint| {
+            endpoint.handle(|client, _params| {
+                client.json(&jsonway::array(|items| {
+                    for i in 0..50 {
+                        items.push(format!("item number {}", i));
+                    }
+                }).unwrap())
+            })
+        });
+
+        api.get("item", |endpoint| {
+            endpoint.handle(|client, _params| client.json(&jsonway::object(|json| json.set("id", 1)).unwrap()))
+        });
+
+        api.get("archive", |endpoint| {
+            endpoint.name("archive");
+            endpoint.handle(|client, _params| {
+                client.json(&jsonway::array(|items| {
+                    for i in 0..50 { items.push(i); }
+                }).unwrap())
+            })
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/items").ok().unwrap();
+    assert!(resp_header!(response, "Content-Encoding").is_none());
+    assert_eq!(resp_header!(response, "Vary").unwrap(), "Accept-Encoding");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/item", |rq| {
+        rq.headers_mut().set_raw("Accept-Encoding", vec![b"gzip".to_vec()]);
+    }).ok().unwrap();
+    assert!(resp_header!(response, "Content-Encoding").is_none());
+    assert_eq!(resp_body!(response), "{\"id\":1}");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/archive", |rq| {
+        rq.headers_mut().set_raw("Accept-Encoding", vec![b"gzip".to_vec()]);
+    }).ok().unwrap();
+    assert!(resp_header!(response, "Content-Encoding").is_none());
+    assert!(resp_header!(response, "Vary").is_none());
+}
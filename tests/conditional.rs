@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rustless::server::{header, status};
+use rustless::batteries::conditional::{self, ConditionalGet, IfMatchExt};
+use rustless::{Nesting};
+
+#[test]
+fn it_answers_matching_etags_with_not_modified() {
+    let app = app!(|api| {
+        api.prefix("api");
+        conditional::enable(api, ConditionalGet::new());
+
+        api.get("report", |endpoint| {
+            endpoint.handle(|client, _params| client.text("quarterly report".to_string()))
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/report").ok().unwrap();
+    let etag = resp_header!(response, "ETag").unwrap();
+    assert!(etag.starts_with("W/\""));
+    assert_eq!(resp_body!(response), "quarterly report");
+
+    let etags = format!("\"other\", {}", etag);
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/report", |rq| {
+        rq.headers_mut().set_raw("If-None-Match", vec![etags.as_bytes().to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::NotModified);
+    assert_eq!(resp_header!(response, "ETag").unwrap(), etag);
+    assert!(response.body.is_none());
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/report", |rq| {
+        rq.headers_mut().set_raw("If-None-Match", vec![b"W/\"stale\"".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+}
+
+#[test]
+fn it_uses_validators_set_by_handlers() {
+    let app = app!(|api| {
+        api.prefix("api");
+        conditional::enable(api, ConditionalGet::new());
+
+        api.get("article", |endpoint| {
+            endpoint.handle(|mut client, _params| {
+                client.set_header(header::ETag(header::EntityTag::strong("v7".to_string())));
+                client.set_header(header::LastModified("Sun, 06 Nov 1994 08:49:37 GMT".parse().unwrap()));
+                client.text("article".to_string())
+            })
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/article", |rq| {
+        rq.headers_mut().set_raw("If-None-Match", vec![b"W/\"v7\"".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::NotModified);
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/article", |rq| {
+        rq.headers_mut().set_raw("If-Modified-Since", vec![b"Mon, 07 Nov 1994 08:00:00 GMT".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::NotModified);
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/article", |rq| {
+        rq.headers_mut().set_raw("If-Modified-Since", vec![b"Sat, 05 Nov 1994 08:00:00 GMT".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+}
+
+#[test]
+fn it_can_be_disabled_per_namespace() {
+    let app = app!(|api| {
+        api.prefix("api");
+        conditional::enable(api, ConditionalGet::new());
+
+        api.namespace("live", |live_ns| {
+            conditional::enable(live_ns, ConditionalGet::disabled());
+            live_ns.get("feed", |endpoint| {
+                endpoint.handle(|client, _params| client.text("feed".to_string()))
+            });
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/live/feed", |rq| {
+        rq.headers_mut().set_raw("If-None-Match", vec![b"*".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+    assert!(resp_header!(response, "ETag").is_none());
+}
+
+#[test]
+fn it_requires_matching_if_match_before_the_handler() {
+    let updates = Arc::new(AtomicUsize::new(0));
+    let counter = updates.clone();
+    let app = app!(move |api| {
+        api.put("articles/:id", move |endpoint| {
+            endpoint.requires_if_match(|_client, params| {
+                Ok(match params.find("id").and_then(|id| id.as_str()) {
+                    Some("1") => Some("v7".to_string()),
+                    Some("3") => Some("\"v9\"".to_string()),
+                    _ => None
+                })
+            });
+            endpoint.handle(move |client, _params| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                client.empty()
+            })
+        });
+    });
+
+    let err_resp = call_app!(app, Put, "http://127.0.0.1:3000/articles/1").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::PreconditionRequired);
+
+    let err_resp = call_app!(app, Put, "http://127.0.0.1:3000/articles/1", |rq| {
+        rq.headers_mut().set_raw("If-Match", vec![b"\"v6\"".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::PreconditionFailed);
+
+    // If-Match uses the strong comparison
+    let err_resp = call_app!(app, Put, "http://127.0.0.1:3000/articles/1", |rq| {
+        rq.headers_mut().set_raw("If-Match", vec![b"W/\"v7\"".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::PreconditionFailed);
+
+    let err_resp = call_app!(app, Put, "http://127.0.0.1:3000/articles/2", |rq| {
+        rq.headers_mut().set_raw("If-Match", vec![b"*".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::PreconditionFailed);
+    assert_eq!(updates.load(Ordering::SeqCst), 0);
+
+    call_app!(app, Put, "http://127.0.0.1:3000/articles/1", |rq| {
+        rq.headers_mut().set_raw("If-Match", vec![b"\"v6\", \"v7\"".to_vec()]);
+    }).ok().unwrap();
+    call_app!(app, Put, "http://127.0.0.1:3000/articles/1", |rq| {
+        rq.headers_mut().set_raw("If-Match", vec![b"*".to_vec()]);
+    }).ok().unwrap();
+
+    // Versions already quoted like ETag values
+    call_app!(app, Put, "http://127.0.0.1:3000/articles/3", |rq| {
+        rq.headers_mut().set_raw("If-Match", vec![b"\"v9\"".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(updates.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,191 @@
+use rustless::server::{method, status};
+use rustless::errors;
+use rustless::batteries::cors::{self, Cors};
+use rustless::{Nesting};
+
+#[test]
+fn it_answers_preflight_requests_from_routes() {
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("orders", |orders_ns| {
+            cors::enable(orders_ns, Cors::build(|cors| {
+                cors.allow_origin("https://shop.example");
+                cors.allow_headers(&["Content-Type", "X-Api-Key"]);
+                cors.allow_credentials(true);
+                cors.max_age(600);
+            }));
+
+            orders_ns.get(":id", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+
+            orders_ns.put(":id", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+
+            orders_ns.delete(":id", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+        });
+    });
+
+    let response = call_app!(app, Options, "http://127.0.0.1:3000/api/orders/1", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://shop.example".to_vec()]);
+        rq.headers_mut().set_raw("Access-Control-Request-Method", vec![b"PUT".to_vec()]);
+    }).ok().unwrap();
+
+    assert_eq!(response.status, status::StatusCode::NoContent);
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Origin").unwrap(), "https://shop.example");
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Methods").unwrap(), "GET, PUT, DELETE, OPTIONS");
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Headers").unwrap(), "Content-Type, X-Api-Key");
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Credentials").unwrap(), "true");
+    assert_eq!(resp_header!(response, "Access-Control-Max-Age").unwrap(), "600");
+    assert_eq!(resp_header!(response, "Vary").unwrap(), "Origin");
+
+    // Origins that are not allowed get no CORS headers nor the methods
+    let response = call_app!(app, Options, "http://127.0.0.1:3000/api/orders/1", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://evil.example".to_vec()]);
+    }).ok().unwrap();
+    assert!(resp_header!(response, "Access-Control-Allow-Origin").is_none());
+    assert!(resp_header!(response, "Allow").is_none());
+    assert_eq!(resp_header!(response, "Vary").unwrap(), "Origin");
+
+    // Paths without routes are not found
+    let err_resp = call_app!(app, Options, "http://127.0.0.1:3000/api/orders/1/items").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::NotFound);
+}
+
+#[test]
+fn it_adds_cors_headers_to_responses() {
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("orders", |orders_ns| {
+            cors::enable(orders_ns, Cors::build(|cors| {
+                cors.allow_origin_wildcard("https://*.example.com");
+                cors.allow_origin_if(|origin| origin == "http://localhost:8080");
+                cors.allow_methods(vec![method::Method::Get]);
+                cors.expose_headers(&["X-Total-Count"]);
+            }));
+
+            orders_ns.get(":id", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+
+            orders_ns.put(":id", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/orders/1", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://admin.example.com".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Origin").unwrap(), "https://admin.example.com");
+    assert_eq!(resp_header!(response, "Access-Control-Expose-Headers").unwrap(), "X-Total-Count");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/orders/1", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"http://localhost:8080".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Origin").unwrap(), "http://localhost:8080");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/orders/1", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://example.org".to_vec()]);
+    }).ok().unwrap();
+    assert!(resp_header!(response, "Access-Control-Allow-Origin").is_none());
+    assert_eq!(resp_header!(response, "Vary").unwrap(), "Origin");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/orders/1").ok().unwrap();
+    assert!(resp_header!(response, "Access-Control-Allow-Origin").is_none());
+    assert_eq!(resp_header!(response, "Vary").unwrap(), "Origin");
+
+    let response = call_app!(app, Options, "http://127.0.0.1:3000/api/orders/1", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://admin.example.com".to_vec()]);
+        rq.headers_mut().set_raw("Access-Control-Request-Headers", vec![b"X-Requested-With".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Methods").unwrap(), "GET");
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Headers").unwrap(), "X-Requested-With");
+}
+
+#[test]
+fn it_allows_any_origin() {
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("orders", |orders_ns| {
+            cors::enable(orders_ns, Cors::build(|cors| cors.allow_any_origin()));
+
+            orders_ns.get(":id", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/orders/1", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://anywhere.example".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_header!(response, "Access-Control-Allow-Origin").unwrap(), "*");
+    assert!(resp_header!(response, "Vary").is_none());
+}
+
+#[test]
+#[should_panic(expected = "CORS credentials can't be allowed for any origin")]
+fn it_rejects_credentials_for_any_origin() {
+    app!(|api| {
+        cors::enable(api, Cors::build(|cors| {
+            cors.allow_any_origin();
+            cors.allow_credentials(true);
+        }));
+    });
+}
+
+#[test]
+fn it_adds_cors_headers_to_error_responses() {
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("orders", |orders_ns| {
+            cors::enable(orders_ns, Cors::build(|cors| cors.allow_origin("https://shop.example")));
+
+            orders_ns.get(":id", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+
+            orders_ns.post("", |endpoint| {
+                endpoint.params(|params| {
+                    params.req_typed("amount", ::valico::json_dsl::u64())
+                });
+                endpoint.handle(|client, _params| client.error(errors::Forbidden { reason: "Closed".to_string() }))
+            });
+        });
+    });
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/orders", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://shop.example".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::BadRequest);
+    assert_eq!(resp_header!(err_resp.response, "Access-Control-Allow-Origin").unwrap(), "https://shop.example");
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/orders?amount=10", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://shop.example".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Forbidden);
+    assert_eq!(resp_header!(err_resp.response, "Access-Control-Allow-Origin").unwrap(), "https://shop.example");
+
+    // Paths without routes
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/orders/1/items", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://shop.example".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::NotFound);
+    assert_eq!(resp_header!(err_resp.response, "Access-Control-Allow-Origin").unwrap(), "https://shop.example");
+
+    // Malformed bodies
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/orders", |rq| {
+        rq.headers_mut().set_raw("Origin", vec![b"https://shop.example".to_vec()]);
+        rq.headers_mut().set_raw("Content-Type", vec![b"application/json".to_vec()]);
+        rq.push_string("{ not a json".to_string());
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::BadRequest);
+    assert_eq!(resp_header!(err_resp.response, "Access-Control-Allow-Origin").unwrap(), "https://shop.example");
+}
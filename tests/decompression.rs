@@ -0,0 +1,110 @@
+use std::io::{self, Write};
+use flate2::Compression;
+use flate2::write::{GzEncoder, ZlibEncoder};
+
+use rustless::server::{header, status};
+use rustless::{Nesting};
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn zlib(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn it_decodes_compressed_json_bodies() {
+    let app = app!(|api| {
+        api.post("orders", |endpoint| {
+            endpoint.handle(|client, params| {
+                let count = params.find("items").and_then(|items| items.as_array()).map_or(0, |items| items.len());
+                client.text(count.to_string())
+            })
+        });
+    });
+
+    let response = call_app!(app, Post, "http://127.0.0.1:3000/orders", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.headers_mut().set_raw("Content-Encoding", vec![b"gzip".to_vec()]);
+        rq.body = Box::new(io::Cursor::new(gzip(b"{\"items\": [1, 2, 3]}")));
+    }).ok().unwrap();
+    assert_eq!(resp_body!(response), "3");
+
+    let response = call_app!(app, Post, "http://127.0.0.1:3000/orders", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.headers_mut().set_raw("Content-Encoding", vec![b"deflate, gzip".to_vec()]);
+        rq.body = Box::new(io::Cursor::new(gzip(&zlib(b"{\"items\": [1, 2]}"))));
+    }).ok().unwrap();
+    assert_eq!(resp_body!(response), "2");
+}
+
+#[test]
+fn it_rejects_unsupported_encodings() {
+    let app = app!(|api| {
+        api.post("orders", |endpoint| edp_stub_handler!(endpoint));
+    });
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/orders", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.headers_mut().set_raw("Content-Encoding", vec![b"br".to_vec()]);
+        rq.push_string("{}".to_string());
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::UnsupportedMediaType);
+    assert_eq!(err_resp.response.headers.get_raw("Accept-Encoding").unwrap()[0], b"gzip, deflate".to_vec());
+}
+
+#[test]
+fn it_limits_the_decoded_body_size() {
+    let mut app = app!(|api| {
+        api.post("orders", |endpoint| edp_stub_handler!(endpoint));
+    });
+    app.max_body_size(1024);
+
+    // Compresses to a few dozen bytes
+    let mut bomb = b"{\"items\": [\"".to_vec();
+    bomb.extend(vec![b'a'; 100_000]);
+    bomb.extend(b"\"]}".iter());
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/orders", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.headers_mut().set_raw("Content-Encoding", vec![b"gzip".to_vec()]);
+        rq.body = Box::new(io::Cursor::new(gzip(&bomb)));
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::PayloadTooLarge);
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/orders", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.push_string(String::from_utf8(bomb.clone()).unwrap());
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::PayloadTooLarge);
+}
+
+#[test]
+fn it_lets_handlers_read_the_body_again() {
+    let app = app!(|api| {
+        api.post("orders", |endpoint| {
+            endpoint.handle(|client, _params| {
+                let body = client.request.read_to_end().unwrap().unwrap_or(String::new());
+                client.text(body)
+            })
+        });
+    });
+
+    let response = call_app!(app, Post, "http://127.0.0.1:3000/orders", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.push_string("{\"items\": [1]}".to_string());
+    }).ok().unwrap();
+    assert_eq!(resp_body!(response), "{\"items\": [1]}");
+
+    let response = call_app!(app, Post, "http://127.0.0.1:3000/orders", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.headers_mut().set_raw("Content-Encoding", vec![b"gzip".to_vec()]);
+        rq.body = Box::new(io::Cursor::new(gzip(b"{\"items\": [1, 2]}")));
+    }).ok().unwrap();
+    assert_eq!(resp_body!(response), "{\"items\": [1, 2]}");
+}
@@ -0,0 +1,198 @@
+use std::error;
+use std::error::Error as StdError;
+use std::fmt;
+use serde_json::value::{Value};
+use valico::json_dsl;
+
+use rustless::server::header;
+use rustless::server::status;
+use rustless::server::mime;
+use rustless::errors::{HttpError};
+use rustless::{Nesting};
+
+#[derive(Debug)]
+pub struct PaymentRequiredError;
+
+impl error::Error for PaymentRequiredError {
+    fn description(&self) -> &str {
+        return "PaymentRequired";
+    }
+}
+
+impl fmt::Display for PaymentRequiredError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.description().fmt(formatter)
+    }
+}
+
+impl HttpError for PaymentRequiredError {
+    fn status(&self) -> status::StatusCode {
+        status::StatusCode::PaymentRequired
+    }
+
+    fn headers(&self) -> header::Headers {
+        let mut headers = header::Headers::new();
+        headers.set_raw("X-Payment-Url", vec![b"https://example.com/pay".to_vec()]);
+        headers
+    }
+
+    fn public_message(&self) -> Option<String> {
+        Some("Please top up your account".to_string())
+    }
+}
+
+#[test]
+fn it_describes_validation_errors_in_json() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.get("users/:user_id", |endpoint| {
+            endpoint.params(|params| {
+                params.req_typed("user_id", json_dsl::u64());
+                params.req_typed("profile", json_dsl::string());
+            });
+
+            edp_stub_handler!(endpoint)
+        })
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/users/Skywalker").err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::BadRequest);
+
+    {
+        let &header::ContentType(ref mime_type): &header::ContentType = response.headers.get().unwrap();
+        assert_eq!(*mime_type, mime::Mime(mime::TopLevel::Application, mime::SubLevel::Json, vec![]));
+    }
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("code").unwrap().as_str().unwrap(), "validation");
+
+    let errors = body.find("errors").unwrap().as_array().unwrap();
+    assert_eq!(errors.len(), 2);
+
+    let paths: Vec<&str> = errors.iter().map(|error| error.find("path").unwrap().as_str().unwrap()).collect();
+    assert!(paths.contains(&"/user_id"));
+    assert!(paths.contains(&"/profile"));
+
+    for error in errors.iter() {
+        assert!(error.find("code").is_some());
+        assert!(error.find("message").is_some());
+    }
+}
+
+#[test]
+fn it_describes_validation_errors_in_plain_text() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.get("users/:user_id", |endpoint| {
+            endpoint.params(|params| {
+                params.req_typed("user_id", json_dsl::u64());
+            });
+
+            edp_stub_handler!(endpoint)
+        })
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/users/Skywalker", |rq| {
+        rq.headers_mut().set(header::Accept(vec![header::qitem(mime!("text/plain"))]));
+    }).err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::BadRequest);
+
+    let body = resp_body!(response);
+    assert!(body.starts_with("Request parameters are invalid"));
+    assert!(body.contains("/user_id: "));
+}
+
+#[test]
+fn it_weighs_accepted_error_formats() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.get("users/:user_id", |endpoint| {
+            endpoint.params(|params| {
+                params.req_typed("user_id", json_dsl::u64());
+            });
+
+            edp_stub_handler!(endpoint)
+        })
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/users/Skywalker", |rq| {
+        rq.headers_mut().set_raw("Accept", vec![b"application/json;q=0.1, text/plain".to_vec()]);
+    }).err().unwrap();
+    let response = err_resp.response;
+    assert!(resp_body!(response).starts_with("Request parameters are invalid"));
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/users/Skywalker", |rq| {
+        rq.headers_mut().set_raw("Accept", vec![b"text/html, */*;q=0.8, text/plain;q=0.5".to_vec()]);
+    }).err().unwrap();
+    let response = err_resp.response;
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("code").unwrap().as_str().unwrap(), "validation");
+}
+
+#[test]
+fn it_describes_body_errors() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+        api.post("users", |endpoint| edp_stub_handler!(endpoint));
+    });
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/users", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.push_string("{ not a json".to_string());
+    }).err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::BadRequest);
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("code").unwrap().as_str().unwrap(), "body");
+    assert!(body.find("message").unwrap().as_str().unwrap().starts_with("Invalid JSON"));
+}
+
+#[test]
+fn it_leaves_not_found_responses_empty() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+        edp_stub!(api);
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/missing").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::NotFound);
+    assert!(err_resp.response.body.is_none());
+}
+
+#[test]
+fn it_consults_registered_http_errors() {
+
+    let mut app = app!(|api| {
+        api.prefix("api");
+        api.get("report", |endpoint| {
+            endpoint.handle(|client, _params| {
+                client.error(PaymentRequiredError)
+            })
+        });
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/report").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::InternalServerError);
+
+    app.http_error::<PaymentRequiredError>();
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/report").err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::PaymentRequired);
+    assert_eq!(response.headers.get_raw("X-Payment-Url").unwrap()[0], b"https://example.com/pay".to_vec());
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("code").unwrap().as_str().unwrap(), "payment_required");
+    assert_eq!(body.find("message").unwrap().as_str().unwrap(), "Please top up your account");
+}
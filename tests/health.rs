@@ -0,0 +1,100 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use serde_json::{self, Value};
+
+use rustless::server::status;
+use rustless::batteries::health::{self, Health, CheckResult};
+use rustless::json::ToJson;
+use rustless::{Nesting};
+
+fn report(body: String) -> Value {
+    serde_json::from_str(&body).unwrap()
+}
+
+#[test]
+fn it_reports_liveness_and_readiness() {
+    let app = app!(|api| {
+        api.prefix("api");
+        api.mount(health::create_api("health", Health::build(|health| {
+            health.liveness("threads", || CheckResult::up());
+            health.readiness("database", || CheckResult::down("Connection refused".to_json()));
+        })));
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/health/live").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+    let body = report(resp_body!(response));
+    assert_eq!(body.find("status").unwrap().as_str().unwrap(), "up");
+    assert!(body.find_path(&["checks", "database"]).is_none());
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/health/ready").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::ServiceUnavailable);
+    let body = report(resp_body!(response));
+    assert_eq!(body.find("status").unwrap().as_str().unwrap(), "down");
+    assert_eq!(body.find_path(&["checks", "threads", "status"]).unwrap().as_str().unwrap(), "up");
+    assert_eq!(body.find_path(&["checks", "database", "details"]).unwrap().as_str().unwrap(), "Connection refused");
+}
+
+#[test]
+fn it_times_out_slow_checks() {
+    let app = app!(|api| {
+        api.mount(health::create_api("health", Health::build(|health| {
+            health.readiness("search", || {
+                thread::sleep(Duration::from_secs(2));
+                CheckResult::up()
+            });
+            health.timeout("search", Duration::from_millis(50));
+        })));
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/health/ready").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::ServiceUnavailable);
+    let body = report(resp_body!(response));
+    assert_eq!(body.find_path(&["checks", "search", "details"]).unwrap().as_str().unwrap(), "Timed out after 50ms");
+}
+
+#[test]
+fn it_does_not_restart_checks_that_are_still_running() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let app = app!(move |api| {
+        api.mount(health::create_api("health", Health::build(move |health| {
+            health.liveness("hung", move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(500));
+                CheckResult::up()
+            });
+            health.timeout("hung", Duration::from_millis(20));
+        })));
+    });
+
+    for _ in 0..3 {
+        let response = call_app!(app, Get, "http://127.0.0.1:3000/health/live").ok().unwrap();
+        assert_eq!(response.status, status::StatusCode::ServiceUnavailable);
+    }
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn it_caches_results() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let counter = runs.clone();
+    let app = app!(move |api| {
+        api.mount(health::create_api("health", Health::build(move |health| {
+            health.liveness("counter", move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                CheckResult::up()
+            });
+            health.cache_for(Duration::from_secs(60));
+        })));
+    });
+
+    call_app!(app, Get, "http://127.0.0.1:3000/health/live").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/health/live").ok().unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    call_app!(app, Get, "http://127.0.0.1:3000/health/ready").ok().unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,103 @@
+use rustless::server::status;
+use rustless::errors;
+use rustless::{Nesting, JsonValue};
+use serde_json::to_value;
+
+trait Paginate: Send + Sync {
+    fn per_page(&self) -> u64;
+}
+
+struct SmallPages;
+
+impl Paginate for SmallPages {
+    fn per_page(&self) -> u64 { 10 }
+}
+
+struct LargePages;
+
+impl Paginate for LargePages {
+    fn per_page(&self) -> u64 { 100 }
+}
+
+#[test]
+fn it_calls_the_innermost_named_helper() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.helper("current_user", |_client, _args| {
+            Ok(to_value("guest"))
+        });
+
+        api.get("me", |endpoint| {
+            endpoint.handle(|mut client, _params| {
+                let user = try!(client.helper("current_user", &JsonValue::Null));
+                client.text(user.as_str().unwrap().to_string())
+            })
+        });
+
+        api.namespace("admin", |admin_ns| {
+            admin_ns.helper("current_user", |_client, _args| {
+                Ok(to_value("admin"))
+            });
+
+            admin_ns.get("me", |endpoint| {
+                endpoint.handle(|mut client, _params| {
+                    let user = try!(client.helper("current_user", &JsonValue::Null));
+                    client.text(user.as_str().unwrap().to_string())
+                })
+            });
+        });
+
+        api.get("missing", |endpoint| {
+            endpoint.handle(|mut client, _params| {
+                try!(client.helper("paginate", &JsonValue::Null));
+                client.empty()
+            })
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/me").ok().unwrap();
+    assert_eq!(resp_body!(response), "guest");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/admin/me").ok().unwrap();
+    assert_eq!(resp_body!(response), "admin");
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/missing").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::InternalServerError);
+    assert_eq!(err_resp.error.downcast::<errors::MissingHelper>().unwrap().name, "paginate");
+}
+
+#[test]
+fn it_shares_helper_objects() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.helpers(Box::new(SmallPages) as Box<Paginate>);
+
+        api.get("users", |endpoint| {
+            endpoint.handle(|client, _params| {
+                let per_page = try!(client.helpers::<Box<Paginate>>()).per_page();
+                client.text(per_page.to_string())
+            })
+        });
+
+        api.namespace("reports", |reports_ns| {
+            reports_ns.helpers(Box::new(LargePages) as Box<Paginate>);
+
+            reports_ns.get("", |endpoint| {
+                endpoint.handle(|client, _params| {
+                    let per_page = try!(client.helpers::<Box<Paginate>>()).per_page();
+                    client.text(per_page.to_string())
+                })
+            });
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/users").ok().unwrap();
+    assert_eq!(resp_body!(response), "10");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/reports").ok().unwrap();
+    assert_eq!(resp_body!(response), "100");
+}
@@ -0,0 +1,35 @@
+use rustless::server::status;
+use rustless::batteries::metrics::{self, Metrics};
+use rustless::{Nesting};
+
+#[test]
+fn it_exposes_metrics_by_route_template() {
+    let mut app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("users/:user_id", |user_ns| {
+            user_ns.get("", |endpoint| {
+                endpoint.handle(|client, _params| client.empty())
+            });
+            user_ns.delete("", |endpoint| {
+                endpoint.handle(|client, _params| client.error(::rustless::errors::NotFound))
+            });
+        });
+
+        api.mount(metrics::create_api("metrics"));
+    });
+    metrics::enable(&mut app, Metrics::new());
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/users/1").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/users/2").ok().unwrap();
+    call_app!(app, Delete, "http://127.0.0.1:3000/api/users/3").err().unwrap();
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/metrics").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+    let text = resp_body!(response);
+    assert!(text.contains("# TYPE rustless_requests_total counter\n"));
+    assert!(text.contains("rustless_requests_total{route=\"/api/users/:user_id\",method=\"GET\",version=\"\",status=\"2xx\"} 2\n"));
+    assert!(text.contains("rustless_requests_total{route=\"/api/users/:user_id\",method=\"DELETE\",version=\"\",status=\"4xx\"} 1\n"));
+    assert!(text.contains("rustless_request_duration_seconds_count{route=\"/api/users/:user_id\",method=\"GET\",version=\"\",status=\"2xx\"} 2\n"));
+    assert!(!text.contains("/api/users/1"));
+}
@@ -0,0 +1,171 @@
+use std::sync::{Arc, Mutex};
+
+use rustless::server::status;
+use rustless::framework::{Around, Next};
+use rustless::framework::client::{Client, ClientResult};
+use rustless::errors;
+use rustless::json::{JsonValue};
+use rustless::{self, Nesting};
+
+struct Trace {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>
+}
+
+impl Around for Trace {
+    fn call<'a, 'n>(&self, client: Client<'a>, params: &mut JsonValue, next: Next<'n>) -> ClientResult<'a> {
+        self.log.lock().unwrap().push(format!("enter {}", self.name));
+        let result = next.run(client, params);
+        self.log.lock().unwrap().push(format!("leave {}", self.name));
+        result
+    }
+}
+
+#[test]
+fn it_composes_around_middleware_in_tree_order() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let api_log = log.clone();
+    let ns_log = log.clone();
+    let edp_log = log.clone();
+    let handler_log = log.clone();
+
+    let app = app!(|api| {
+        api.prefix("api");
+        api.around_middleware(Trace { name: "api", log: api_log });
+
+        api.namespace("users", |users| {
+            users.around_middleware(Trace { name: "namespace", log: ns_log });
+
+            users.get("", |endpoint| {
+                endpoint.around_middleware(Trace { name: "endpoint", log: edp_log });
+                endpoint.handle(move |client, _params| {
+                    handler_log.lock().unwrap().push("handler".to_string());
+                    client.empty()
+                })
+            });
+        });
+    });
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/users").ok().unwrap();
+
+    assert_eq!(*log.lock().unwrap(), vec![
+        "enter api", "enter namespace", "enter endpoint",
+        "handler",
+        "leave endpoint", "leave namespace", "leave api"
+    ]);
+}
+
+#[test]
+fn it_allows_around_middleware_to_short_circuit() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.around(|client, _params, _next| {
+            client.text("From cache".to_string())
+        });
+
+        api.before(|_client, _params| {
+            panic!("Callbacks must not run");
+        });
+
+        api.get("info", |endpoint| {
+            endpoint.handle(|_client, _params| {
+                panic!("Handler must not run");
+            })
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/info").ok().unwrap();
+    assert_eq!(resp_body!(response), "From cache");
+}
+
+#[test]
+fn it_allows_around_middleware_to_transform_results() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.around(|client, params, next| {
+            let mut client = try!(next.run(client, params));
+            client.set_status(status::StatusCode::Accepted);
+            Ok(client)
+        });
+
+        api.namespace("missing", |missing| {
+            missing.around(|client, params, next| {
+                match next.run(client, params) {
+                    Err(err_resp) => {
+                        if err_resp.error.is::<errors::NotFound>() {
+                            Err(err_resp)
+                        } else {
+                            Err(rustless::ErrorResponse {
+                                error: Box::new(errors::NotFound),
+                                response: None
+                            })
+                        }
+                    },
+                    ok => ok
+                }
+            });
+
+            missing.get("", |endpoint| {
+                endpoint.handle(|client, _params| {
+                    client.error(errors::Body::new("Broken".to_string()))
+                })
+            });
+        });
+
+        edp_stub!(api);
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/info").ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Accepted);
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/missing").err().unwrap();
+    assert!(err_resp.error.is::<errors::NotFound>());
+    assert_eq!(err_resp.response.status, status::StatusCode::NotFound);
+}
+
+#[test]
+fn it_allows_around_middleware_to_retry() {
+    let attempts = Arc::new(Mutex::new(0i32));
+    let handler_attempts = attempts.clone();
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.around(|mut client, params, next| {
+            let mut retries = 0;
+            loop {
+                match next.run_borrowed(&mut client, params) {
+                    Ok(()) => return Ok(client),
+                    Err(_) if retries < 2 => retries += 1,
+                    Err(err_resp) => return Err(err_resp)
+                }
+            }
+        });
+
+        api.get("flaky", |endpoint| {
+            endpoint.handle(move |client, _params| {
+                let mut attempts = handler_attempts.lock().unwrap();
+                *attempts += 1;
+                if *attempts < 3 {
+                    client.error(errors::NotFound)
+                } else {
+                    client.text("Done".to_string())
+                }
+            })
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/flaky").ok().unwrap();
+    assert_eq!(resp_body!(response), "Done");
+    assert_eq!(*attempts.lock().unwrap(), 3);
+
+    // Gives up after two retries
+    *attempts.lock().unwrap() = -10;
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/flaky").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::NotFound);
+    assert_eq!(*attempts.lock().unwrap(), -7);
+}
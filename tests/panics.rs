@@ -0,0 +1,121 @@
+use std::sync::{Arc, Mutex};
+
+use rustless::server::status;
+use rustless::errors;
+use rustless::{self, Nesting};
+
+#[test]
+fn it_catches_panics_in_handlers() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("users", |users| {
+            users.get(":id", |endpoint| {
+                endpoint.handle(|_client, _params| {
+                    panic!("Database is gone")
+                })
+            });
+        });
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/users/1").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::InternalServerError);
+
+    let panic = err_resp.error.downcast::<errors::Panic>().unwrap();
+    assert_eq!(panic.message, "Database is gone");
+    assert_eq!(panic.route, "/api/users/:id");
+}
+
+#[test]
+fn it_passes_panics_in_callbacks_to_error_formatters() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.error_formatter(|err, _media| {
+            if err.is::<errors::Panic>() {
+                Some(rustless::Response::from(status::StatusCode::ServiceUnavailable, Box::new("Try again later")))
+            } else {
+                None
+            }
+        });
+
+        api.before(|_client, _params| {
+            let user_id: Option<u64> = None;
+            user_id.expect("User must be loaded");
+            Ok(())
+        });
+
+        edp_stub!(api);
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/info").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::ServiceUnavailable);
+    assert_eq!(err_resp.error.downcast::<errors::Panic>().unwrap().message, "User must be loaded");
+}
+
+#[derive(Debug)]
+struct StaleRecord;
+
+impl ::std::error::Error for StaleRecord {
+    fn description(&self) -> &str {
+        "StaleRecord"
+    }
+}
+
+impl ::std::fmt::Display for StaleRecord {
+    fn fmt(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        "StaleRecord".fmt(formatter)
+    }
+}
+
+#[test]
+fn it_catches_panics_in_rescue_handlers() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.rescue_from::<StaleRecord, _>(|_err, _client| {
+            panic!("Conflict page is missing")
+        });
+
+        api.get("orders", |endpoint| {
+            endpoint.handle(|client, _params| {
+                client.error(StaleRecord)
+            })
+        });
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/orders").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::InternalServerError);
+    assert_eq!(err_resp.error.downcast::<errors::Panic>().unwrap().message, "Conflict page is missing");
+}
+
+#[test]
+fn it_catches_panics_in_ensure_callbacks() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let api_log = log.clone();
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.ensure(move |_client, _params, outcome| {
+            api_log.lock().unwrap().push(outcome.is_ok());
+        });
+
+        api.namespace("users", |users| {
+            users.ensure(|_client, _params, _outcome| {
+                panic!("Audit log is gone")
+            });
+
+            users.get(":id", |endpoint| {
+                endpoint.handle(|client, _params| client.text("Luke".to_string()))
+            });
+        });
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/users/1").ok().unwrap();
+    assert_eq!(resp_body!(response), "Luke");
+    assert_eq!(*log.lock().unwrap(), vec![true]);
+}
@@ -0,0 +1,144 @@
+use std::error;
+use std::error::Error as StdError;
+use std::fmt;
+use serde_json::value::{Value};
+use valico::json_dsl;
+
+use rustless::server::header;
+use rustless::server::status;
+use rustless::batteries::problem::{self, Problems};
+use rustless::{Nesting};
+
+#[derive(Debug)]
+pub struct OutOfCreditError {
+    balance: u64
+}
+
+impl error::Error for OutOfCreditError {
+    fn description(&self) -> &str {
+        return "OutOfCredit";
+    }
+}
+
+impl fmt::Display for OutOfCreditError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.description().fmt(formatter)
+    }
+}
+
+#[test]
+fn it_formats_validation_errors_as_problems() {
+    let app = app!(|api| {
+        api.prefix("api");
+        problem::enable(api, Problems::new());
+
+        api.get("users/:user_id", |endpoint| {
+            endpoint.params(|params| {
+                params.req_typed("user_id", json_dsl::u64());
+            });
+
+            edp_stub_handler!(endpoint)
+        });
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/users/Skywalker").err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::BadRequest);
+
+    {
+        let &header::ContentType(ref mime_type): &header::ContentType = response.headers.get().unwrap();
+        assert_eq!(*mime_type, problem::content_type());
+    }
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("type").unwrap().as_str().unwrap(), "about:blank");
+    assert_eq!(body.find("status").unwrap().as_u64().unwrap(), 400);
+    assert_eq!(body.find("instance").unwrap().as_str().unwrap(), "/api/users/Skywalker");
+    assert_eq!(body.find("errors").unwrap().as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn it_formats_not_found_as_problem() {
+    let app = app!(|api| {
+        api.prefix("api");
+        problem::enable(api, Problems::new());
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/missing").err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::NotFound);
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("title").unwrap().as_str().unwrap(), "Not Found");
+}
+
+#[test]
+fn it_allows_custom_errors_to_extend_problems() {
+    let app = app!(|api| {
+        api.prefix("api");
+        problem::enable(api, Problems::build(|problems| {
+            problems.extend::<OutOfCreditError, _>(|err, problem| {
+                problem.type_ = "https://example.com/probs/out-of-credit".to_string();
+                problem.title = "You do not have enough credit.".to_string();
+                problem.status = status::StatusCode::Forbidden;
+                problem.set_extension("balance", Value::U64(err.balance));
+            });
+        }));
+
+        api.post("purchase", |endpoint| {
+            endpoint.handle(|client, _params| {
+                client.error(OutOfCreditError { balance: 30 })
+            })
+        });
+    });
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/purchase").err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::Forbidden);
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("type").unwrap().as_str().unwrap(), "https://example.com/probs/out-of-credit");
+    assert_eq!(body.find("status").unwrap().as_u64().unwrap(), 403);
+    assert_eq!(body.find("balance").unwrap().as_u64().unwrap(), 30);
+}
+
+#[test]
+fn it_formats_malformed_requests_as_problems() {
+    let app = app!(|api| {
+        api.prefix("api");
+        problem::enable(api, Problems::new());
+
+        api.post("purchase", |endpoint| {
+            endpoint.handle(|client, _params| {
+                client.error(OutOfCreditError { balance: 30 })
+            })
+        });
+    });
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/purchase", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.push_string("{ not a json".to_string());
+    }).err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::BadRequest);
+    {
+        let &header::ContentType(ref mime_type): &header::ContentType = response.headers.get().unwrap();
+        assert_eq!(*mime_type, problem::content_type());
+    }
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("status").unwrap().as_u64().unwrap(), 400);
+    assert!(body.find("detail").unwrap().as_str().unwrap().starts_with("Invalid JSON"));
+    assert_eq!(body.find("instance").unwrap().as_str().unwrap(), "/api/purchase");
+
+    // Requests that match no endpoint are reported the same way
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/missing", |rq| {
+        rq.headers_mut().set(header::ContentType(mime!("application/json")));
+        rq.push_string("[1, 2".to_string());
+    }).err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::BadRequest);
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert!(body.find("detail").unwrap().as_str().unwrap().starts_with("Invalid JSON"));
+}
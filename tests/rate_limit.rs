@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use rustless::server::status;
+use rustless::errors;
+use rustless::batteries::auth::api_key::KeyLocation;
+use rustless::batteries::rate_limit::{self, Algorithm, KeyExtractor, MemoryStore, RateLimiter};
+use rustless::{Nesting};
+
+#[test]
+fn it_limits_requests_by_remote_addr() {
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.namespace("search", |search_ns| {
+            search_ns.around_middleware(RateLimiter::new(
+                Algorithm::SlidingWindow { limit: 2, window_seconds: 60 },
+                KeyExtractor::RemoteAddr
+            ));
+            edp_stub!(search_ns);
+            search_ns.get("broken", |endpoint| {
+                endpoint.handle(|client, _params| client.error(errors::NotFound))
+            });
+        });
+
+        edp_stub!(api);
+        rate_limit::enable(api);
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/search/info").ok().unwrap();
+    assert_eq!(resp_header!(response, "RateLimit-Limit").unwrap(), "2");
+    assert_eq!(resp_header!(response, "RateLimit-Remaining").unwrap(), "1");
+    assert_eq!(resp_header!(response, "RateLimit-Reset").unwrap(), "60");
+
+    // Errors without a response of their own get the headers too
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/search/broken").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::NotFound);
+    assert_eq!(resp_header!(err_resp.response, "RateLimit-Remaining").unwrap(), "0");
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/search/info").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::TooManyRequests);
+    assert!(resp_header!(err_resp.response, "Retry-After").is_some());
+    assert_eq!(resp_header!(err_resp.response, "RateLimit-Remaining").unwrap(), "0");
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/search/info", |rq| {
+        rq.set_remote_str("10.0.0.2:4000");
+    }).ok().unwrap();
+    assert_eq!(response.status, status::StatusCode::Ok);
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/info").ok().unwrap();
+    assert!(resp_header!(response, "RateLimit-Limit").is_none());
+}
+
+#[test]
+fn it_limits_requests_by_api_key() {
+    let app = app!(|api| {
+        api.prefix("api");
+        api.get("info", |endpoint| {
+            endpoint.around_middleware(RateLimiter::new(
+                Algorithm::TokenBucket { capacity: 1, refill_per_second: 0.01 },
+                KeyExtractor::ApiKey(KeyLocation::Header("X-Api-Key".to_string()))
+            ));
+            edp_stub_handler!(endpoint)
+        });
+        rate_limit::enable(api);
+    });
+
+    for key in &[b"k-acme", b"k-inco"] {
+        call_app!(app, Get, "http://127.0.0.1:3000/api/info", |rq| {
+            rq.headers_mut().set_raw("X-Api-Key", vec![key.to_vec()]);
+        }).ok().unwrap();
+    }
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/info", |rq| {
+        rq.headers_mut().set_raw("X-Api-Key", vec![b"k-acme".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::TooManyRequests);
+    assert_eq!(resp_header!(err_resp.response, "Retry-After").unwrap(), "100");
+
+    // Requests without a key are not limited
+    call_app!(app, Get, "http://127.0.0.1:3000/api/info").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/info").ok().unwrap();
+}
+
+#[test]
+fn it_limits_requests_by_custom_keys() {
+    let app = app!(|api| {
+        api.prefix("api");
+        api.around_middleware(RateLimiter::new(
+            Algorithm::SlidingWindow { limit: 1, window_seconds: 60 },
+            KeyExtractor::Custom(Box::new(|client| Some(client.endpoint.path.path.clone())))
+        ));
+        edp_stub!(api);
+        api.get("status", |endpoint| edp_stub_handler!(endpoint));
+        rate_limit::enable(api);
+    });
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/info").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/status").ok().unwrap();
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/status").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::TooManyRequests);
+}
+
+#[test]
+fn it_counts_limiters_sharing_a_store_apart() {
+    let store = Arc::new(MemoryStore::new());
+
+    let app = app!(|api| {
+        api.prefix("api");
+        for path in &["search", "orders"] {
+            api.get(path, |endpoint| {
+                let mut limiter = RateLimiter::new(
+                    Algorithm::SlidingWindow { limit: 1, window_seconds: 60 },
+                    KeyExtractor::RemoteAddr
+                );
+                limiter.store(store.clone());
+                endpoint.around_middleware(limiter);
+                edp_stub_handler!(endpoint)
+            });
+        }
+    });
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/search").ok().unwrap();
+    call_app!(app, Get, "http://127.0.0.1:3000/api/orders").ok().unwrap();
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/orders").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::TooManyRequests);
+    assert_eq!(store.len(), 2);
+}
@@ -0,0 +1,87 @@
+use rustless::errors;
+use rustless::server::status;
+use rustless::batteries::request_id::{self, RequestId, RequestIdExt};
+use rustless::{Nesting, Response};
+
+#[test]
+fn it_echoes_incoming_request_ids() {
+    let mut app = app!(|api| {
+        api.prefix("api");
+        api.get("echo", |endpoint| {
+            endpoint.handle(|client, _params| {
+                let id = client.request_id().unwrap().to_string();
+                client.text(id)
+            })
+        });
+    });
+    request_id::enable(&mut app, RequestId::new());
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/echo", |rq| {
+        rq.headers_mut().set_raw("X-Request-Id", vec![b"req-42".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_header!(response, "X-Request-Id").unwrap(), "req-42");
+    assert_eq!(resp_body!(response), "req-42");
+}
+
+#[test]
+fn it_generates_request_ids() {
+    let mut app = app!(|api| {
+        api.prefix("api");
+        api.get("echo", |endpoint| {
+            endpoint.handle(|client, _params| {
+                let id = client.request_id().unwrap().to_string();
+                client.text(id)
+            })
+        });
+    });
+    request_id::enable(&mut app, RequestId::new());
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/echo").ok().unwrap();
+    let id = resp_header!(response, "X-Request-Id").unwrap();
+    assert_eq!(id.len(), 36);
+    assert_eq!(resp_body!(response), id);
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/echo", |rq| {
+        rq.headers_mut().set_raw("X-Request-Id", vec![b"two words".to_vec()]);
+    }).ok().unwrap();
+    assert_eq!(resp_header!(response, "X-Request-Id").unwrap().len(), 36);
+}
+
+#[test]
+fn it_passes_request_ids_to_error_formatters() {
+    let mut app = app!(|api| {
+        api.prefix("api");
+
+        api.error_formatter_with_context(|_err, context| {
+            let mut response = Response::new(status::StatusCode::NotFound);
+            let id = context.request.request_id().unwrap_or("none").to_string();
+            response.replace_body(Box::new(format!("Failed request {}", id)));
+            Some(response)
+        });
+
+        api.get("missing", |endpoint| {
+            endpoint.handle(|_client, _params| {
+                Err(rustless::ErrorResponse{
+                    error: Box::new(errors::NotFound) as Box<errors::Error + Send>,
+                    response: None
+                })
+            })
+        });
+    });
+    request_id::enable(&mut app, RequestId::build(|request_id| {
+        request_id.header("X-Correlation-Id");
+        request_id.trust_incoming(false);
+        request_id.generator(|| "generated".to_string());
+    }));
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/missing", |rq| {
+        rq.headers_mut().set_raw("X-Correlation-Id", vec![b"req-42".to_vec()]);
+    }).err().unwrap();
+    assert_eq!(resp_header!(err_resp.response, "X-Correlation-Id").unwrap(), "generated");
+    let response = err_resp.response;
+    assert_eq!(resp_body!(response), "Failed request generated");
+
+    // Requests matching no route are echoed too
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/unknown").err().unwrap();
+    assert_eq!(resp_header!(err_resp.response, "X-Correlation-Id").unwrap(), "generated");
+}
@@ -0,0 +1,165 @@
+use std::error;
+use std::error::Error as StdError;
+use std::fmt;
+use serde_json::value::{Value};
+use jsonway;
+
+use rustless::server::header;
+use rustless::server::status;
+use rustless::{self, Nesting};
+use rustless::errors::{Error};
+
+#[derive(Debug)]
+pub struct RecordNotFoundError {
+    id: String
+}
+
+impl error::Error for RecordNotFoundError {
+    fn description(&self) -> &str {
+        return "RecordNotFound";
+    }
+}
+
+impl fmt::Display for RecordNotFoundError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.description().fmt(formatter)
+    }
+}
+
+#[derive(Debug)]
+pub struct ConflictError;
+
+impl error::Error for ConflictError {
+    fn description(&self) -> &str {
+        return "Conflict";
+    }
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.description().fmt(formatter)
+    }
+}
+
+#[test]
+fn it_rescues_errors_by_type() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.rescue_from::<RecordNotFoundError, _>(|err, mut client| {
+            client.set_status(status::StatusCode::NotFound);
+            client.set_header(header::CacheControl(vec![header::CacheDirective::NoCache]));
+            client.json(&jsonway::object(|json| {
+                json.set("missing", err.id.clone());
+            }).unwrap())
+        });
+
+        api.namespace("users", |users| {
+            users.get(":id", |endpoint| {
+                endpoint.handle(|client, params| {
+                    let id = params.find("id").unwrap().as_str().unwrap().to_string();
+                    client.error(RecordNotFoundError { id: id })
+                })
+            });
+
+            users.post(":id", |endpoint| {
+                endpoint.handle(|client, _params| {
+                    client.error(ConflictError)
+                })
+            });
+        });
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/users/42").err().unwrap();
+    let response = err_resp.response;
+    assert!(err_resp.error.is::<RecordNotFoundError>());
+    assert_eq!(response.status, status::StatusCode::NotFound);
+    assert!(response.headers.get::<header::CacheControl>().is_some());
+
+    let body: Value = resp_body!(response).parse().unwrap();
+    assert_eq!(body.find("missing").unwrap().as_str().unwrap(), "42");
+
+    // Errors without a handler are left for error formatters
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/users/42").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::InternalServerError);
+}
+
+#[test]
+fn it_prefers_innermost_rescue_handlers() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.rescue_from::<ConflictError, _>(|_err, client| {
+            client.text("api".to_string())
+        });
+
+        api.namespace("users", |users| {
+            users.rescue_from::<ConflictError, _>(|_err, client| {
+                client.text("namespace".to_string())
+            });
+
+            users.post("", |endpoint| {
+                endpoint.handle(|client, _params| {
+                    client.error(ConflictError)
+                })
+            });
+
+            users.put("", |endpoint| {
+                endpoint.rescue_from::<ConflictError, _>(|_err, mut client| {
+                    client.set_status(status::StatusCode::Conflict);
+                    client.text("endpoint".to_string())
+                });
+
+                endpoint.handle(|client, _params| {
+                    client.error(ConflictError)
+                })
+            });
+        });
+
+        api.delete("users", |endpoint| {
+            endpoint.handle(|client, _params| {
+                client.error(ConflictError)
+            })
+        });
+    });
+
+    let err_resp = call_app!(app, Post, "http://127.0.0.1:3000/api/users").err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(resp_body!(response), "namespace");
+
+    let err_resp = call_app!(app, Put, "http://127.0.0.1:3000/api/users").err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(response.status, status::StatusCode::Conflict);
+    assert_eq!(resp_body!(response), "endpoint");
+
+    let err_resp = call_app!(app, Delete, "http://127.0.0.1:3000/api/users").err().unwrap();
+    let response = err_resp.response;
+    assert_eq!(resp_body!(response), "api");
+}
+
+#[test]
+fn it_rescues_errors_from_callbacks() {
+
+    let app = app!(|api| {
+        api.prefix("api");
+
+        api.rescue_from::<ConflictError, _>(|_err, mut client| {
+            client.set_status(status::StatusCode::Conflict);
+            client.empty()
+        });
+
+        api.before(|_client, _params| {
+            Err(rustless::ErrorResponse{
+                error: Box::new(ConflictError) as Box<Error + Send>,
+                response: None
+            })
+        });
+
+        edp_stub!(api);
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/info").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::Conflict);
+}
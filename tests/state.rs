@@ -0,0 +1,111 @@
+use std::sync::{Arc, Mutex};
+use rustless::server::status;
+use rustless::errors;
+use rustless::{Nesting};
+
+struct Greeting(String);
+
+struct Missing;
+
+#[derive(Clone)]
+struct CurrentUser {
+    name: String
+}
+
+#[test]
+fn it_provides_application_state() {
+
+    let mut app = app!(|api| {
+        api.prefix("api");
+
+        api.get("greeting", |endpoint| {
+            endpoint.handle(|client, _params| {
+                let greeting = try!(client.state::<Greeting>());
+                client.text(greeting.0.clone())
+            })
+        });
+
+        api.get("missing", |endpoint| {
+            endpoint.handle(|client, _params| {
+                try!(client.state::<Missing>());
+                client.empty()
+            })
+        });
+    });
+
+    app.state.insert(Greeting("Hello".to_string()));
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/greeting").ok().unwrap();
+    assert_eq!(resp_body!(response), "Hello");
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/api/missing").err().unwrap();
+    assert_eq!(err_resp.response.status, status::StatusCode::InternalServerError);
+
+    let missing = err_resp.error.downcast::<errors::MissingDependency>().unwrap();
+    assert!(missing.type_name.ends_with("Missing"));
+}
+
+#[test]
+fn it_builds_request_scoped_values_once_and_cleans_them_up() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let provide_log = log.clone();
+    let cleanup_log = log.clone();
+
+    let mut app = app!(|api| {
+        api.prefix("api");
+
+        api.before(|client, _params| {
+            try!(client.inject::<CurrentUser>());
+            Ok(())
+        });
+
+        api.get("me", |endpoint| {
+            endpoint.handle(|mut client, _params| {
+                let user = try!(client.inject::<CurrentUser>()).clone();
+                client.text(user.name)
+            })
+        });
+
+        api.get("fail", |endpoint| {
+            endpoint.handle(|client, _params| {
+                client.error(errors::NotFound)
+            })
+        });
+    });
+
+    app.state.insert(Greeting("admin".to_string()));
+
+    app.provide_with_cleanup(move |client| {
+        provide_log.lock().unwrap().push("provide".to_string());
+        let name = try!(client.state::<Greeting>()).0.clone();
+        Ok(CurrentUser { name: name })
+    }, move |user: CurrentUser, outcome| {
+        cleanup_log.lock().unwrap().push(format!("cleanup {} {}", user.name, outcome.is_ok()));
+    });
+
+    let response = call_app!(app, Get, "http://127.0.0.1:3000/api/me").ok().unwrap();
+    assert_eq!(resp_body!(response), "admin");
+
+    call_app!(app, Get, "http://127.0.0.1:3000/api/fail").err().unwrap();
+
+    assert_eq!(*log.lock().unwrap(), vec![
+        "provide", "cleanup admin true",
+        "provide", "cleanup admin false"
+    ]);
+}
+
+#[test]
+fn it_fails_when_no_provider_is_registered() {
+
+    let app = app!(|api| {
+        api.get("me", |endpoint| {
+            endpoint.handle(|mut client, _params| {
+                try!(client.inject::<CurrentUser>());
+                client.empty()
+            })
+        });
+    });
+
+    let err_resp = call_app!(app, Get, "http://127.0.0.1:3000/me").err().unwrap();
+    assert!(err_resp.error.is::<errors::MissingDependency>());
+}
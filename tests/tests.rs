@@ -9,6 +9,9 @@ extern crate valico;
 extern crate jsonway;
 extern crate serde;
 extern crate serde_json;
+extern crate ring;
+extern crate rustc_serialize;
+extern crate flate2;
 
 #[macro_export]
 macro_rules! sr {
@@ -42,6 +45,13 @@ macro_rules! resp_body {
     }
 }
 
+#[macro_export]
+macro_rules! resp_header {
+    ($resp:expr, $name:expr) => {
+        $resp.headers.get_raw($name).map(|values| String::from_utf8(values[0].clone()).unwrap())
+    }
+}
+
 #[macro_export]
 macro_rules! mime {
     ($mime:expr) => ($mime.parse().unwrap())
@@ -77,3 +87,23 @@ mod prefix;
 mod redirect;
 mod callbacks;
 mod serializers;
+mod errors;
+mod problem;
+mod rescue;
+mod panics;
+mod middleware;
+mod state;
+mod helpers;
+mod auth;
+mod jwt;
+mod api_key;
+mod cors;
+mod rate_limit;
+mod request_id;
+mod access_log;
+mod metrics;
+mod health;
+mod compression;
+mod decompression;
+mod conditional;
+mod cache;